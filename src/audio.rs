@@ -0,0 +1,291 @@
+use event::Event;
+use rand::SeedableRng;
+use rand::distributions::{Distribution, Range};
+use rodio::Source;
+use rodio::buffer::SamplesBuffer;
+use rodio::source::Buffered;
+use specs::World;
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+const SAMPLE_RATE: u32 = 44100;
+/// Same seed for every run so sounds are always the same
+const SEED: [u8; 16] = [115, 111, 117, 110, 100, 32, 111, 102, 32, 115, 112, 97, 99, 101, 33, 33];
+/// Distance between the ears of listeners
+const EAR_DISTANCE: f32 = 0.2;
+/// Distance of the thrust behind the ship
+const THRUST_DISTANCE: f32 = 0.1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, EnumIterator)]
+enum Sound {
+    RocketLaunch,
+    Explosion,
+    TargetCapture,
+}
+
+struct Backend {
+    device: ::rodio::Device,
+    sounds: HashMap<Sound, Buffered<SamplesBuffer<f32>>>,
+    thrust: Buffered<SamplesBuffer<f32>>,
+    /// Thrust of each player
+    thrusts: [Option<::rodio::SpatialSink>; 3],
+    music: ::rodio::Sink,
+}
+
+/// Play sounds of the world, does nothing without backend
+pub struct Audio {
+    backend: Option<Backend>,
+}
+
+impl Audio {
+    /// Use the default output device
+    pub fn new() -> Result<Self, String> {
+        let device = ::rodio::default_output_device()
+            .ok_or_else(|| "No audio output device found, sound is disabled".to_string())?;
+
+        let mut rng = ::rand::prng::XorShiftRng::from_seed(SEED);
+        let sounds = Sound::iter_variants()
+            .map(|sound| {
+                let samples = match sound {
+                    Sound::RocketLaunch => rocket_launch(&mut rng),
+                    Sound::Explosion => explosion(&mut rng),
+                    Sound::TargetCapture => target_capture(),
+                };
+                (sound, SamplesBuffer::new(1, SAMPLE_RATE, samples).buffered())
+            })
+            .collect();
+
+        let music = ::rodio::Sink::new(&device);
+        music.append(SamplesBuffer::new(1, SAMPLE_RATE, music_loop()).repeat_infinite());
+
+        Ok(Audio {
+            backend: Some(Backend {
+                sounds,
+                thrust: SamplesBuffer::new(1, SAMPLE_RATE, thrust(&mut rng)).buffered(),
+                thrusts: [None, None, None],
+                music,
+                device,
+            }),
+        })
+    }
+
+    /// Backend that plays nothing, for headless runs
+    pub fn null() -> Self {
+        Audio { backend: None }
+    }
+
+    /// Play the sounds of the events of the frame and follow the thrust of players
+    pub fn update(&mut self, world: &World) {
+        let events = world.read_resource::<::event::FrameEvents>();
+        let configuration = world.read_resource::<::configuration::Configuration>();
+        let physic_world = world.read_resource::<::resource::PhysicWorld>();
+        let players_entities = world.read_resource::<::resource::PlayersEntities>();
+        let bodies = world.read_storage::<::component::PhysicBody>();
+        let flight_controls = world.read_storage::<::component::FlightControl>();
+
+        // Each split screen player listens with the ears of its ship
+        let mut listeners = [None; 3];
+        for (listener, entity) in listeners.iter_mut().zip(players_entities.iter()) {
+            *listener = entity
+                .and_then(|entity| bodies.get(entity))
+                .map(|body| body.get(&physic_world).position().clone());
+        }
+
+        let sounds = spatial_sounds(&events, &listeners);
+
+        let backend = match self.backend {
+            Some(ref mut backend) => backend,
+            None => return,
+        };
+
+        backend.music.set_volume(configuration.music_volume);
+
+        for (sound, emitter, left_ear, right_ear) in sounds {
+            let sink = ::rodio::SpatialSink::new(&backend.device, emitter, left_ear, right_ear);
+            sink.set_volume(configuration.effects_volume);
+            sink.append(backend.sounds[&sound].clone());
+            sink.detach();
+        }
+
+        for (player, thrust) in backend.thrusts.iter_mut().enumerate() {
+            let flight_control = players_entities[player]
+                .and_then(|entity| flight_controls.get(entity));
+            let (listener, flight_control) = match (listeners[player], flight_control) {
+                (Some(listener), Some(flight_control)) => (listener, flight_control),
+                _ => {
+                    // Dropping the sink stops it
+                    *thrust = None;
+                    continue;
+                }
+            };
+
+            let emitter = listener * ::na::Point3::new(-THRUST_DISTANCE, 0.0, 0.0);
+            let (left_ear, right_ear) = ears(&listener);
+            if thrust.is_none() {
+                let sink = ::rodio::SpatialSink::new(&backend.device, array(&emitter.coords), left_ear, right_ear);
+                sink.append(backend.thrust.clone().repeat_infinite());
+                *thrust = Some(sink);
+            }
+            let sink = thrust.as_ref().unwrap();
+            sink.set_emitter_position(array(&emitter.coords));
+            sink.set_left_ear_position(left_ear);
+            sink.set_right_ear_position(right_ear);
+            sink.set_volume(configuration.effects_volume * flight_control.power.max(0.0).min(1.0));
+        }
+    }
+}
+
+/// Sound of each event with its emitter and the ears of the closest listener
+fn spatial_sounds(
+    events: &[Event],
+    listeners: &[Option<::na::Isometry3<f32>>; 3],
+) -> Vec<(Sound, [f32; 3], [f32; 3], [f32; 3])> {
+    events.iter()
+        .filter_map(|event| match *event {
+            Event::RocketLaunched { position } => Some((Sound::RocketLaunch, position.translation.vector)),
+            Event::Explosion { position } => Some((Sound::Explosion, position)),
+            Event::TargetCaptured { position } => Some((Sound::TargetCapture, position)),
+            _ => None,
+        })
+        .map(|(sound, position)| {
+            let listener = listeners.iter()
+                .filter_map(|listener| *listener)
+                .min_by(|a, b| {
+                    let a = (a.translation.vector - position).norm();
+                    let b = (b.translation.vector - position).norm();
+                    a.partial_cmp(&b).unwrap()
+                });
+            let (left_ear, right_ear) = match listener {
+                Some(listener) => ears(&listener),
+                None => (array(&position), array(&position)),
+            };
+            (sound, array(&position), left_ear, right_ear)
+        })
+        .collect()
+}
+
+fn ears(listener: &::na::Isometry3<f32>) -> ([f32; 3], [f32; 3]) {
+    let left = listener * ::na::Point3::new(0.0, EAR_DISTANCE / 2.0, 0.0);
+    let right = listener * ::na::Point3::new(0.0, -EAR_DISTANCE / 2.0, 0.0);
+    (array(&left.coords), array(&right.coords))
+}
+
+fn array(vector: &::na::Vector3<f32>) -> [f32; 3] {
+    [vector[0], vector[1], vector[2]]
+}
+
+/// Sample a signal of the given duration
+fn synthesize<F: FnMut(f32) -> f32>(duration: f32, mut signal: F) -> Vec<f32> {
+    (0..(duration * SAMPLE_RATE as f32) as usize)
+        .map(|i| signal(i as f32 / SAMPLE_RATE as f32))
+        .collect()
+}
+
+/// Filtered noise, the amount of filtering is given at each instant in [0, 1]
+fn noise<R: ::rand::Rng, F: Fn(f32) -> f32>(rng: &mut R, duration: f32, smoothing: F) -> Vec<f32> {
+    let range = Range::new(-1.0f32, 1.0);
+    let mut filtered = 0.0f32;
+    synthesize(duration, |t| {
+        let smoothing = smoothing(t);
+        filtered = smoothing * filtered + (1.0 - smoothing) * range.sample(&mut *rng);
+        filtered
+    })
+}
+
+/// Rumble looping without clicks
+fn thrust<R: ::rand::Rng>(rng: &mut R) -> Vec<f32> {
+    let duration = 1.0;
+    let mut samples = noise(rng, duration, |_| 0.97);
+    let fade = (0.05 * SAMPLE_RATE as f32) as usize;
+    let len = samples.len();
+    for i in 0..fade {
+        let t = i as f32 / fade as f32;
+        samples[i] = samples[i] * t + samples[len - fade + i] * (1.0 - t);
+    }
+    samples.truncate(len - fade);
+    samples.iter().map(|sample| sample * 3.0).collect()
+}
+
+/// Whoosh with a falling whistle
+fn rocket_launch<R: ::rand::Rng>(rng: &mut R) -> Vec<f32> {
+    let duration = 0.6;
+    let whoosh = noise(rng, duration, |t| 0.6 + 0.35 * t / duration);
+    let mut phase = 0.0f32;
+    synthesize(duration, |t| {
+        phase += 2.0 * PI * (1200.0 - 800.0 * t / duration) / SAMPLE_RATE as f32;
+        let envelope = (t / 0.03).min(1.0) * (1.0 - t / duration);
+        envelope * (0.6 * whoosh[(t * SAMPLE_RATE as f32) as usize % whoosh.len()] + 0.2 * phase.sin())
+    })
+}
+
+/// Noise burst getting darker as it decays
+fn explosion<R: ::rand::Rng>(rng: &mut R) -> Vec<f32> {
+    let duration = 1.2;
+    let burst = noise(rng, duration, |t| 0.8 + 0.19 * t / duration);
+    synthesize(duration, |t| {
+        let envelope = (t / 0.005).min(1.0) * (-4.0 * t).exp();
+        envelope * 2.5 * burst[(t * SAMPLE_RATE as f32) as usize % burst.len()]
+    })
+}
+
+/// Two rising notes
+fn target_capture() -> Vec<f32> {
+    synthesize(0.4, |t| {
+        let (frequency, start) = if t < 0.1 { (880.0, 0.0) } else { (1320.0, 0.1) };
+        let envelope = (-12.0 * (t - start)).exp();
+        0.4 * envelope * (2.0 * PI * frequency * t).sin()
+    })
+}
+
+/// Slow arpeggio over four chords
+fn music_loop() -> Vec<f32> {
+    const NOTE_DURATION: f32 = 0.25;
+    // Semitones from A3 of each chord
+    const CHORDS: [[i32; 3]; 4] = [[0, 3, 7], [-4, 0, 3], [-7, -3, 0], [-2, 2, 5]];
+
+    let notes_per_chord = 8;
+    let duration = NOTE_DURATION * (notes_per_chord * CHORDS.len()) as f32;
+    synthesize(duration, |t| {
+        let note = (t / NOTE_DURATION) as usize;
+        let chord = CHORDS[note / notes_per_chord];
+        let semitone = chord[note % 3] + 12 * ((note % notes_per_chord) / 3 % 2) as i32;
+        let frequency = 220.0 * 2f32.powf(semitone as f32 / 12.0);
+        let bass = 110.0 * 2f32.powf(chord[0] as f32 / 12.0) / 2.0;
+
+        let note_time = t - note as f32 * NOTE_DURATION;
+        let envelope = (note_time / 0.01).min(1.0) * (-6.0 * note_time).exp();
+        0.15 * envelope * ((2.0 * PI * frequency * t).sin() + 0.3 * (4.0 * PI * frequency * t).sin())
+            + 0.08 * (2.0 * PI * bass * t).sin()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{spatial_sounds, Audio, Sound};
+    use event::Event;
+
+    fn events() -> Vec<Event> {
+        vec![
+            Event::RocketLaunched { position: ::na::Isometry3::new(::na::Vector3::new(1.0, 0.0, 0.0), ::na::zero()) },
+            Event::Explosion { position: ::na::Vector3::new(2.0, 0.0, 0.0) },
+            Event::TargetCaptured { position: ::na::Vector3::new(3.0, 0.0, 0.0) },
+        ]
+    }
+
+    #[test]
+    fn null_update() {
+        let mut world = ::create_world(::configuration::Configuration::built_in().unwrap());
+        world.write_resource::<::event::FrameEvents>().extend(events());
+        Audio::null().update(&world);
+    }
+
+    #[test]
+    fn sounds_of_events() {
+        let listener = ::na::Isometry3::new(::na::Vector3::new(2.0, 0.0, 0.0), ::na::zero());
+        let sounds = spatial_sounds(&events(), &[None, Some(listener), None]);
+        let kinds = sounds.iter().map(|&(sound, ..)| sound).collect::<Vec<_>>();
+        assert_eq!(kinds, vec![Sound::RocketLaunch, Sound::Explosion, Sound::TargetCapture]);
+        assert_eq!(sounds[2].1, [3.0, 0.0, 0.0]);
+        assert_eq!(sounds[1].2, [2.0, 0.1, 0.0]);
+    }
+}
@@ -1,7 +1,7 @@
 const GEN_PALE_DIVISION: usize = 10;
 const GEN_PALE_DELTA: f32 = 0.0;
 
-#[derive(EnumIterator)]
+#[derive(Clone, Copy, EnumIterator)]
 #[repr(usize)]
 pub enum GenPale {
     Color0,
@@ -21,24 +21,30 @@ pub enum GenPale {
 #[test]
 fn alignment() {
     assert!(GenPale::iter_variants().count() == GEN_PALE_DIVISION + 2);
-    assert!(GenPale::Black as usize = GEN_PALE_DIVISION);
+    assert!(GenPale::Black as usize == GEN_PALE_DIVISION);
 }
 
 impl GenPale {
-    // all colors, not black nor white
     pub fn colors() -> Vec<Self> {
         GenPale::iter_variants().collect::<Vec<_>>()
     }
 }
 
-lazy_static! {
-   static ref  GEN_PALE_GENERATION: Vec<[f32; 3]> =
-       generate_colors(GEN_PALE_DIVISION, GEN_PALE_DELTA, ::CFG.color_black, ::CFG.color_white);
-}
+/// Colors of GenPale generated from the configuration, to be recreated when it is reloaded
+pub struct Palette(Vec<[f32; 3]>);
+
+impl Palette {
+    pub fn new(configuration: &::configuration::Configuration) -> Self {
+        Palette(generate_colors(
+            GEN_PALE_DIVISION,
+            GEN_PALE_DELTA,
+            configuration.color_black,
+            configuration.color_white,
+        ))
+    }
 
-impl Into<[f32; 3]> for GenPale {
-    fn into(self) -> [f32; 3] {
-        GEN_PALE_GENERATION[self as usize]
+    pub fn rgb(&self, color: GenPale) -> [f32; 3] {
+        self.0[color as usize]
     }
 }
 
@@ -46,12 +52,6 @@ fn generate_colors(division: usize, delta: f32, black: f32, white: f32) -> Vec<[
     let mut colors = vec![];
     assert!(black < white);
 
-    // Black
-    colors.push([black, black, black]);
-
-    // White
-    colors.push([white, white, white]);
-
     for i in 0..division {
         let color = color_circle((i as f32 + delta) / division as f32);
         colors.push([
@@ -61,6 +61,12 @@ fn generate_colors(division: usize, delta: f32, black: f32, white: f32) -> Vec<[
         ]);
     }
 
+    // Black
+    colors.push([black, black, black]);
+
+    // White
+    colors.push([white, white, white]);
+
     // Convert from Srgb to Unorm
     for color in &mut colors {
         let lin_color = ::palette::LinSrgb::new(color[0], color[1], color[2]);
@@ -1,5 +1,5 @@
 use retained_storage::RetainedStorage;
-use std::any::Any;
+use std::collections::VecDeque;
 
 pub struct RocketLauncher {
     pub position: ::na::Isometry3<f32>,
@@ -9,9 +9,9 @@ impl ::specs::Component for RocketLauncher {
     type Storage = ::specs::VecStorage<Self>;
 }
 impl RocketLauncher {
-    pub fn new(position: ::na::Isometry3<f32>) -> Self {
+    pub fn new(position: ::na::Isometry3<f32>, timer: f32) -> Self {
         RocketLauncher {
-            timer: 0.0,
+            timer,
             position,
         }
     }
@@ -55,6 +55,23 @@ impl ::specs::Component for PlayerKiller {
     type Storage = ::specs::NullStorage<Self>;
 }
 
+/// Last positions of a moving body, most recent first
+pub struct Trail {
+    pub positions: VecDeque<::na::Vector3<f32>>,
+    pub timer: f32,
+}
+impl ::specs::Component for Trail {
+    type Storage = ::specs::VecStorage<Self>;
+}
+impl Trail {
+    pub fn new() -> Self {
+        Trail {
+            positions: VecDeque::new(),
+            timer: 0.0,
+        }
+    }
+}
+
 pub struct FlightControl {
     pub x_direction: f32,
     pub y_direction: f32,
@@ -69,6 +86,31 @@ impl ::specs::Component for FlightControl {
     type Storage = ::specs::VecStorage<Self>;
 }
 
+impl FlightControl {
+    pub fn new(configuration: &::configuration::Configuration) -> Self {
+        let mut flight_control = FlightControl {
+            x_direction: 0.0,
+            y_direction: 0.0,
+            power: 0.0,
+            ang_damping: 0.0,
+            lin_damping: 0.0,
+            power_force: 0.0,
+            default_power_force: 0.0,
+            direction_force: 0.0,
+        };
+        flight_control.reset_configuration(configuration);
+        flight_control
+    }
+
+    pub fn reset_configuration(&mut self, configuration: &::configuration::Configuration) {
+        self.ang_damping = configuration.flight_control_ang_damping;
+        self.lin_damping = configuration.flight_control_lin_damping;
+        self.power_force = configuration.flight_control_power_force;
+        self.direction_force = configuration.flight_control_direction_force;
+        self.default_power_force = configuration.flight_control_default_power_force;
+    }
+}
+
 #[derive(Default)]
 pub struct Player;
 impl ::specs::Component for Player {
@@ -90,20 +132,15 @@ impl PhysicBody {
         self.handle
     }
 
-    pub fn entity(body: &::nphysics::object::RigidBody<f32>) -> ::specs::Entity {
-        let entity = body.user_data().unwrap();
-        let entity = unsafe { ::std::mem::transmute::<&Box<_>, &Box<Any>>(entity) };
-        entity.downcast_ref::<::specs::Entity>().unwrap().clone()
-    }
-
     pub fn add<'a>(
         entity: ::specs::Entity,
-        mut body: ::nphysics::object::RigidBody<f32>,
+        body: ::nphysics::object::RigidBody<f32>,
         bodies: &mut ::specs::WriteStorage<'a, ::component::PhysicBody>,
         physic_world: &mut ::resource::PhysicWorld,
+        physic_entities: &mut ::resource::PhysicEntities,
     ) {
-        body.set_user_data(Some(Box::new(entity)));
         let bodyhandle = physic_world.add_rigid_body(body);
+        physic_entities.insert_body(bodyhandle, entity);
         bodies.insert(entity, PhysicBody { handle: bodyhandle }).unwrap();
     }
 
@@ -140,20 +177,15 @@ impl PhysicSensor {
         self.handle
     }
 
-    pub fn entity(body: &::nphysics::object::Sensor<f32>) -> ::specs::Entity {
-        let entity = body.user_data().unwrap();
-        let entity = unsafe { ::std::mem::transmute::<&Box<_>, &Box<Any>>(entity) };
-        entity.downcast_ref::<::specs::Entity>().unwrap().clone()
-    }
-
     pub fn add<'a>(
         entity: ::specs::Entity,
-        mut sensor: ::nphysics::object::Sensor<f32>,
+        sensor: ::nphysics::object::Sensor<f32>,
         sensors: &mut ::specs::WriteStorage<'a, ::component::PhysicSensor>,
         physic_world: &mut ::resource::PhysicWorld,
+        physic_entities: &mut ::resource::PhysicEntities,
     ) {
-        sensor.set_user_data(Some(Box::new(entity)));
         let sensorhandle = physic_world.add_sensor(sensor);
+        physic_entities.insert_sensor(sensorhandle, entity);
         sensors.insert(
             entity,
             PhysicSensor {
@@ -170,6 +202,15 @@ impl PhysicSensor {
         physic_world.sensor(self.handle)
     }
 
+    /// Absolute position, the relative position is only absolute for sensors without parent
+    pub fn position(&self, physic_world: &::resource::PhysicWorld) -> ::na::Isometry3<f32> {
+        let sensor = self.get(physic_world);
+        match sensor.parent() {
+            Some(parent) => physic_world.rigid_body(parent.clone()).position() * sensor.relative_position(),
+            None => sensor.relative_position().clone(),
+        }
+    }
+
     #[inline]
     pub fn get_mut<'a>(
         &'a mut self,
@@ -179,13 +220,6 @@ impl PhysicSensor {
     }
 }
 
-pub fn physic_world_object_entity(wo: &::nphysics::object::WorldObject, physic_world: &::nphysics::world::World<f32>) -> ::specs::Entity {
-    match wo {
-        &::nphysics::object::WorldObject::RigidBody(wo) => PhysicBody::entity(physic_world.rigid_body(wo)),
-        &::nphysics::object::WorldObject::Sensor(wo) => PhysicSensor::entity(physic_world.sensor(wo)),
-    }
-}
-
 pub type Contact = ::ncollide::query::Contact<::na::Point3<f32>>;
 
 pub struct Contactor {
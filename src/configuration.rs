@@ -1,47 +1,325 @@
-use std::fs::File;
+use specs::Join;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+use app_dirs2::{app_root, AppDataType};
 
 const FILENAME: &str = "configuration.ron";
+const DEFAULT: &str = include_str!("configuration.ron");
+const WATCH_PERIOD_MS: u64 = 500;
 
-lazy_static! {
-    pub static ref CFG: Configuration = {
-        let file = File::open(FILENAME).unwrap();
-        let c: Configuration = ::ron::de::from_reader(file).unwrap();
-        c.check();
-        c
-    };
+/// Deserialize a value present in a layer
+fn some<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: ::serde::Deserializer<'de>,
+    T: ::serde::Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+/// Serialize a value present in a layer, absent ones are skipped
+fn inner<S, T>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: ::serde::Serializer,
+    T: ::serde::Serialize,
+{
+    ::serde::Serialize::serialize(value.as_ref().unwrap(), serializer)
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct Configuration {
-    pub fps: usize,
+macro_rules! configuration {
+    ($($field:ident: $ty:ty,)*) => {
+        #[derive(Clone, Serialize, Deserialize)]
+        pub struct Configuration {
+            $(pub $field: $ty,)*
+        }
+
+        /// A configuration file that may only define some fields
+        #[derive(Default, Serialize, Deserialize)]
+        #[serde(rename = "Configuration")]
+        struct ConfigurationLayer {
+            $(
+                #[serde(
+                    default,
+                    deserialize_with = "some",
+                    serialize_with = "inner",
+                    skip_serializing_if = "Option::is_none"
+                )]
+                $field: Option<$ty>,
+            )*
+        }
+
+        impl Configuration {
+            fn apply(&mut self, layer: ConfigurationLayer) {
+                $(
+                    if let Some(value) = layer.$field {
+                        self.$field = value;
+                    }
+                )*
+            }
+
+            /// Set in the layer the fields that differ from the original
+            fn changes(&self, original: &Configuration, layer: &mut ConfigurationLayer) {
+                $(
+                    if self.$field != original.$field {
+                        layer.$field = Some(self.$field.clone());
+                    }
+                )*
+            }
+        }
+    }
+}
+
+configuration! {
+    fps: usize,
+
+    unlocal_texture_size: u32,
+    unlocal_texture_layers: u32,
 
-    pub unlocal_texture_size: u32,
-    pub unlocal_texture_layers: u32,
+    update_step_time: f32,
+    max_update_steps: usize,
 
-    pub physic_min_step_time: f32,
-    pub physic_max_step_time: f32,
+    flight_control_ang_damping: f32,
+    flight_control_lin_damping: f32,
+    flight_control_power_force: f32,
+    flight_control_direction_force: f32,
+    flight_control_default_power_force: f32,
 
-    pub flight_control_ang_damping: f32,
-    pub flight_control_lin_damping: f32,
-    pub flight_control_power_force: f32,
-    pub flight_control_direction_force: f32,
-    pub flight_control_default_power_force: f32,
+    color_black: f32,
+    color_white: f32,
 
-    pub color_black: f32,
-    pub color_white: f32,
+    rocket_control_lin_damping: f32,
+    rocket_control_force: f32,
 
-    pub rocket_control_lin_damping: f32,
-    pub rocket_control_force: f32,
+    ball_radius: f32,
+    rocket_launcher_timer: f32,
 
-    pub ball_radius: f32,
-    pub rocket_launcher_timer: f32,
+    mine_control_max_force: f32,
+    mine_control_coef_force: f32,
 
-    pub mine_control_max_force: f32,
-    pub mine_control_coef_force: f32,
+    text_scale: f32,
 
-    pub text_scale: f32,
+    trail_length: usize,
+    trail_period: f32,
+
+    outline: bool,
+    outline_width: f32,
+    outline_falloff: f32,
+
+    effects_volume: f32,
+    music_volume: f32,
+
+    minimap: bool,
+    minimap_fog_of_war: bool,
+    minimap_size: f32,
 }
 
 impl Configuration {
-    fn check(&self) {}
+    /// Layers in order of precedence: built-in defaults, user file, local override
+    pub fn load() -> Result<Self, String> {
+        let mut configuration = Configuration::built_in()?;
+
+        for path in &Configuration::paths()? {
+            if let Ok(file) = File::open(path) {
+                let layer: ConfigurationLayer = ::ron::de::from_reader(file)
+                    .map_err(|e| format!("Failed to parse configuration file {}: {}", path.display(), e))?;
+                configuration.apply(layer);
+            }
+        }
+
+        configuration.check().map_err(|errors| {
+            format!("Invalid configuration:\n\n{}", errors.join("\n"))
+        })?;
+
+        Ok(configuration)
+    }
+
+    /// Defaults without any file layer
+    pub fn built_in() -> Result<Self, String> {
+        ::ron::de::from_str(DEFAULT)
+            .map_err(|e| format!("Failed to parse built-in configuration: {}", e))
+    }
+
+    fn user_path() -> Result<PathBuf, String> {
+        let mut path = app_root(AppDataType::UserConfig, &::resource::APP_INFO)
+            .map_err(|e| format!("Failed to get user configuration directory: {}", e))?;
+        path.push(FILENAME);
+        Ok(path)
+    }
+
+    /// Files of the layers that can be modified: user file and local override
+    fn paths() -> Result<Vec<PathBuf>, String> {
+        Ok(vec![Configuration::user_path()?, PathBuf::from(FILENAME)])
+    }
+
+    /// A local override is present in the working directory
+    pub fn local_override() -> bool {
+        PathBuf::from(FILENAME).exists()
+    }
+
+    /// Write to the user file the fields changed since the original, its other fields are kept
+    pub fn save_changes(&self, original: &Configuration) -> Result<(), String> {
+        let path = Configuration::user_path()?;
+        let mut layer = match File::open(&path) {
+            Ok(file) => ::ron::de::from_reader(file)
+                .map_err(|e| format!("Failed to parse configuration file {}: {}", path.display(), e))?,
+            Err(_) => ConfigurationLayer::default(),
+        };
+        self.changes(original, &mut layer);
+
+        let string = ::ron::ser::to_string_pretty(&layer, Default::default())
+            .map_err(|e| format!("Failed to serialize configuration: {}", e))?;
+        let mut file = File::create(&path).map_err(|e| {
+            format!("Failed to create configuration file at {}: {}", path.display(), e)
+        })?;
+        file.write_all(string.as_bytes()).map_err(|e| {
+            format!("Failed to write to configuration file {}: {}", path.display(), e)
+        })
+    }
+
+    /// Return all the errors found
+    pub fn check(&self) -> Result<(), Vec<String>> {
+        let mut errors = vec![];
+
+        if self.fps == 0 {
+            errors.push("fps must be positive".to_string());
+        }
+        if !self.unlocal_texture_size.is_power_of_two() {
+            errors.push(format!("unlocal_texture_size must be a power of two, got {}", self.unlocal_texture_size));
+        }
+        if self.unlocal_texture_layers < 2
+            || 2_u32.checked_pow(self.unlocal_texture_layers - 2)
+                .map_or(true, |factor| factor > self.unlocal_texture_size)
+        {
+            errors.push(format!(
+                "unlocal_texture_layers must be in [2, log2(unlocal_texture_size) + 2], got {}",
+                self.unlocal_texture_layers,
+            ));
+        }
+        if !(self.update_step_time > 0.0) {
+            errors.push("update_step_time must be positive".to_string());
+        }
+        if self.max_update_steps == 0 {
+            errors.push("max_update_steps must be positive".to_string());
+        }
+        for &(name, value) in &[
+            ("flight_control_ang_damping", self.flight_control_ang_damping),
+            ("flight_control_lin_damping", self.flight_control_lin_damping),
+            ("rocket_control_lin_damping", self.rocket_control_lin_damping),
+            ("effects_volume", self.effects_volume),
+            ("music_volume", self.music_volume),
+        ] {
+            if !(value >= 0.0 && value <= 1.0) {
+                errors.push(format!("{} must be in [0, 1], got {}", name, value));
+            }
+        }
+        for &(name, value) in &[
+            ("flight_control_power_force", self.flight_control_power_force),
+            ("flight_control_direction_force", self.flight_control_direction_force),
+            ("flight_control_default_power_force", self.flight_control_default_power_force),
+            ("rocket_control_force", self.rocket_control_force),
+            ("mine_control_max_force", self.mine_control_max_force),
+            ("mine_control_coef_force", self.mine_control_coef_force),
+        ] {
+            if !(value >= 0.0) {
+                errors.push(format!("{} must not be negative, got {}", name, value));
+            }
+        }
+        if !(self.minimap_size > 0.0 && self.minimap_size <= 1.0) {
+            errors.push(format!("minimap_size must be in ]0, 1], got {}", self.minimap_size));
+        }
+        if !(self.color_black >= 0.0 && self.color_black < self.color_white && self.color_white <= 1.0) {
+            errors.push(format!(
+                "colors must verify 0 <= color_black < color_white <= 1, got {} and {}",
+                self.color_black,
+                self.color_white,
+            ));
+        }
+        for &(name, value) in &[
+            ("ball_radius", self.ball_radius),
+            ("rocket_launcher_timer", self.rocket_launcher_timer),
+            ("text_scale", self.text_scale),
+            ("trail_period", self.trail_period),
+            ("outline_width", self.outline_width),
+            ("outline_falloff", self.outline_falloff),
+        ] {
+            if !(value > 0.0) {
+                errors.push(format!("{} must be positive, got {}", name, value));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Reload the configuration when one of its files is modified
+pub struct ConfigurationWatcher {
+    modified: Vec<Option<SystemTime>>,
+    last_check: Instant,
+    /// Error of the last reload, displayed on screen until a successful reload
+    pub error: Option<String>,
+    /// Incremented at each successful reload
+    pub generation: usize,
+}
+
+impl ConfigurationWatcher {
+    pub fn new() -> Self {
+        ConfigurationWatcher {
+            modified: ConfigurationWatcher::modified_times(),
+            last_check: Instant::now(),
+            error: None,
+            generation: 0,
+        }
+    }
+
+    /// Empty if the files can't be located, the error is reported by the reload
+    fn modified_times() -> Vec<Option<SystemTime>> {
+        Configuration::paths()
+            .unwrap_or_default()
+            .iter()
+            .map(|path| fs::metadata(path).and_then(|metadata| metadata.modified()).ok())
+            .collect()
+    }
+
+    /// Return the new configuration if a file has changed and the reload succeeded
+    pub fn poll(&mut self) -> Option<Configuration> {
+        if self.last_check.elapsed() < Duration::from_millis(WATCH_PERIOD_MS) {
+            return None;
+        }
+        self.last_check = Instant::now();
+
+        let modified = ConfigurationWatcher::modified_times();
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+
+        match Configuration::load() {
+            Ok(configuration) => {
+                self.error = None;
+                self.generation += 1;
+                Some(configuration)
+            }
+            Err(error) => {
+                self.error = Some(error);
+                None
+            }
+        }
+    }
+}
+
+/// Replace the configuration if one of its files has been modified
+pub fn reload(world: &::specs::World) {
+    let new_configuration = match world.write_resource::<ConfigurationWatcher>().poll() {
+        Some(configuration) => configuration,
+        None => return,
+    };
+    for flight_control in (&mut world.write_storage::<::component::FlightControl>()).join() {
+        flight_control.reset_configuration(&new_configuration);
+    }
+    *world.write_resource::<Configuration>() = new_configuration;
 }
@@ -0,0 +1,265 @@
+use specs::{Join, World};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Frames shown in the frame time graph
+const GRAPH_FRAMES: usize = 120;
+/// Frame time at the top of the graph in seconds
+const GRAPH_MAX_TIME: f32 = 0.05;
+/// Top left corner and size of the graph
+const GRAPH_ORIGIN: [f32; 2] = [0.3, 0.6];
+const GRAPH_SIZE: [f32; 2] = [0.65, 0.35];
+const GRAPH_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+const GRAPH_BACKGROUND_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.6];
+const GRAPH_TARGET_COLOR: [f32; 4] = [0.9, 0.35, 0.3, 0.8];
+/// Position of the text of the overlay
+pub const TEXT_ORIGIN: [f32; 2] = [0.3, -0.95];
+/// Length of contact normals and size of contact crosses in physic debug mode
+const CONTACT_SIZE: f32 = 0.2;
+const CONTACT_COLOR: [f32; 4] = [1.0, 0.9, 0.1, 1.0];
+const PROXIMITY_COLOR: [f32; 4] = [0.2, 0.9, 0.9, 1.0];
+const CLOSEST_PLAYER_COLOR: [f32; 4] = [0.9, 0.35, 0.3, 1.0];
+const PATH_COLOR: [f32; 4] = [0.35, 0.75, 0.4, 1.0];
+
+/// State of the debug overlay, toggled with [F3]
+#[derive(Default)]
+pub struct Overlay {
+    pub enabled: bool,
+    /// Physic debug mode, toggled with [F4]
+    pub physic: bool,
+    pub fps: usize,
+    /// Duration of the last frames in seconds, oldest first
+    pub frame_times: VecDeque<f32>,
+    /// Duration of the stages of the last frame in seconds
+    pub stages: Vec<(&'static str, f32)>,
+    /// Duration of the systems of the last update in seconds
+    pub systems: Vec<(&'static str, f32)>,
+}
+
+impl Overlay {
+    pub fn push_frame_time(&mut self, frame_time: f32) {
+        self.frame_times.push_back(frame_time);
+        while self.frame_times.len() > GRAPH_FRAMES {
+            self.frame_times.pop_front();
+        }
+    }
+}
+
+/// Duration of the systems, filled by Timed systems running in parallel
+#[derive(Default)]
+pub struct SystemTimings(pub Mutex<Vec<(&'static str, f32)>>);
+
+/// Path of each player to its nearest target in physic debug mode, with the
+/// cells of the player and of the target it was found for. Reset with the maze.
+#[derive(Default)]
+pub struct Paths([Option<(::na::Vector3<isize>, ::na::Vector3<isize>, Option<Vec<::na::Vector3<isize>>>)>; 3]);
+
+/// Number of fixed updates during the last frame
+#[derive(Default, Deref, DerefMut)]
+pub struct UpdateSteps(pub usize);
+
+/// Measure the duration of the system into SystemTimings
+pub struct Timed<S> {
+    name: &'static str,
+    system: S,
+}
+
+impl<S> Timed<S> {
+    pub fn new(name: &'static str, system: S) -> Self {
+        Timed { name, system }
+    }
+}
+
+impl<'a, S: ::specs::System<'a>> ::specs::System<'a> for Timed<S> {
+    type SystemData = (S::SystemData, ::specs::ReadExpect<'a, SystemTimings>);
+
+    fn run(&mut self, (data, timings): Self::SystemData) {
+        let start = Instant::now();
+        self.system.run(data);
+        timings.0.lock().unwrap().push((self.name, seconds(start.elapsed())));
+    }
+}
+
+pub fn seconds(duration: Duration) -> f32 {
+    duration.as_secs() as f32 + duration.subsec_nanos() as f32 / 1_000_000_000.0
+}
+
+/// Move the timings of the systems of the updates of the frame into the overlay, summed by system
+pub fn collect_system_timings(world: &World) {
+    let mut systems = ::std::mem::replace(&mut *world.read_resource::<SystemTimings>().0.lock().unwrap(), vec![]);
+    systems.sort_by_key(|&(name, _)| name);
+    systems.dedup_by(|timing, previous| if timing.0 == previous.0 {
+        previous.1 += timing.1;
+        true
+    } else {
+        false
+    });
+    world.write_resource::<Overlay>().systems = systems;
+}
+
+/// Write the text of the overlay if it is enabled
+pub fn update_text(world: &World) {
+    let overlay = world.read_resource::<Overlay>();
+    if !overlay.enabled {
+        return;
+    }
+
+    let frame_time = overlay.frame_times.back().cloned().unwrap_or(0.0);
+    let max_frame_time = overlay.frame_times.iter().cloned().fold(0.0, f32::max);
+    let mut lines = vec![
+        format!("FPS: {}", overlay.fps),
+        format!("Frame: {:.1} ms (max {:.1} ms)", frame_time * 1000.0, max_frame_time * 1000.0),
+    ];
+    lines.extend(overlay.stages.iter().map(|&(name, time)| format!("{}: {:.2} ms", name, time * 1000.0)));
+    lines.push(String::new());
+    lines.extend(overlay.systems.iter().map(|&(name, time)| format!("{}: {:.2} ms", name, time * 1000.0)));
+    lines.push(format!("update steps: {}", world.read_resource::<UpdateSteps>().0));
+    let time_control = world.read_resource::<::resource::TimeControl>();
+    lines.push(format!(
+        "time scale: x{}{}",
+        time_control.scale,
+        if time_control.frozen { " (frozen)" } else { "" },
+    ));
+    lines.push(String::new());
+
+    let entities = world.entities().join().count();
+    let bodies = world.read_storage::<::component::PhysicBody>().join().count();
+    let players = world.read_storage::<::component::Player>().join().count();
+    let rockets = world.read_storage::<::component::RocketControl>().join().count();
+    let mines = world.read_storage::<::component::MineControl>().join().count();
+    let targets = world.read_storage::<::component::Target>().join().count();
+    lines.push(format!("entities: {} (bodies {})", entities, bodies));
+    lines.push(format!("players {}, rockets {}, mines {}, targets {}", players, rockets, mines, targets));
+
+    let font = world.read_resource::<::resource::Font>();
+    let text_scale = world.read_resource::<::configuration::Configuration>().text_scale * ::hud::TEXT_SCALE;
+    world.write_resource::<::resource::Text>().debug = ::util::notification_layout(lines, &font, text_scale);
+}
+
+/// Frame time graph in the normalized coordinates of the screen, none if disabled
+pub fn graph(world: &World) -> Option<Vec<::hud::HudVertex>> {
+    let overlay = world.read_resource::<Overlay>();
+    if !overlay.enabled {
+        return None;
+    }
+
+    let mut vertices = vec![];
+    let bottom = [GRAPH_ORIGIN[0], GRAPH_ORIGIN[1] + GRAPH_SIZE[1]];
+    ::hud::rectangle(&mut vertices, bottom, GRAPH_SIZE, GRAPH_BACKGROUND_COLOR);
+
+    let bar_width = GRAPH_SIZE[0] / GRAPH_FRAMES as f32;
+    for (i, &frame_time) in overlay.frame_times.iter().enumerate() {
+        let height = (frame_time / GRAPH_MAX_TIME).min(1.0) * GRAPH_SIZE[1];
+        ::hud::rectangle(
+            &mut vertices,
+            [bottom[0] + i as f32 * bar_width, bottom[1]],
+            [bar_width, height],
+            GRAPH_COLOR,
+        );
+    }
+
+    // Frame time aimed by the configuration
+    let fps = world.read_resource::<::configuration::Configuration>().fps;
+    let target = (1.0 / (fps as f32 * GRAPH_MAX_TIME)).min(1.0) * GRAPH_SIZE[1];
+    ::hud::rectangle(
+        &mut vertices,
+        [bottom[0], bottom[1] - target],
+        [GRAPH_SIZE[0], 0.005],
+        GRAPH_TARGET_COLOR,
+    );
+
+    Some(vertices)
+}
+
+/// Contacts, proximities, closest player vectors and paths of players to the
+/// nearest target as line lists with their colors, none if physic debug is disabled
+pub fn physic_lines(world: &World) -> Vec<(Vec<::graphics::LineVertex>, [f32; 4])> {
+    if !world.read_resource::<Overlay>().physic {
+        return vec![];
+    }
+
+    let physic_world = world.read_resource::<::resource::PhysicWorld>();
+    let maze_map = world.read_resource::<::minimap::MazeMap>();
+    let entities = world.entities();
+    let bodies = world.read_storage::<::component::PhysicBody>();
+    let sensors = world.read_storage::<::component::PhysicSensor>();
+    let contactors = world.read_storage::<::component::Contactor>();
+    let proximitors = world.read_storage::<::component::Proximitor>();
+    let closest_players = world.read_storage::<::component::ClosestPlayer>();
+    let targets = world.read_storage::<::component::Target>();
+
+    let position = |entity| {
+        bodies.get(entity)
+            .map(|body| body.get(&physic_world).position().translation.vector)
+            .or_else(|| sensors.get(entity).map(|sensor| sensor.position(&physic_world).translation.vector))
+    };
+
+    let mut contacts = vec![];
+    for contactor in contactors.join() {
+        for &(_, ref contact) in &contactor.contacts {
+            let point = contact.world1.coords;
+            contacts.extend(::minimap::cross(&point, CONTACT_SIZE / 2.0));
+            contacts.push(::graphics::LineVertex::new(&point));
+            contacts.push(::graphics::LineVertex::new(&(point + contact.normal.normalize() * CONTACT_SIZE)));
+        }
+    }
+
+    let mut proximities = vec![];
+    for (entity, proximitor) in (&*entities, &proximitors).join() {
+        if let Some(origin) = position(entity) {
+            for other in proximitor.intersections.iter().filter_map(|&other| position(other)) {
+                proximities.push(::graphics::LineVertex::new(&origin));
+                proximities.push(::graphics::LineVertex::new(&other));
+            }
+        }
+    }
+
+    let mut vectors = vec![];
+    for (entity, closest_player) in (&*entities, &closest_players).join() {
+        if let (Some(origin), Some(vector)) = (position(entity), closest_player.vector) {
+            vectors.push(::graphics::LineVertex::new(&origin));
+            vectors.push(::graphics::LineVertex::new(&(origin + vector)));
+        }
+    }
+
+    let target_cells = (&targets, &sensors).join()
+        .map(|(_, sensor)| maze_map.cell(&sensor.position(&physic_world).translation.vector))
+        .collect::<Vec<_>>();
+    let mut paths = vec![];
+    let mut cache = world.write_resource::<Paths>();
+    let players_entities = world.read_resource::<::resource::PlayersEntities>();
+    for (cached, entity) in cache.0.iter_mut().zip(players_entities.iter()) {
+        let cell = match entity.and_then(|entity| position(entity)) {
+            Some(position) => maze_map.cell(&position),
+            None => {
+                *cached = None;
+                continue;
+            }
+        };
+        let nearest_target = target_cells.iter()
+            .min_by_key(|target| (0..3).map(|i| (target[i] - cell[i]).abs()).sum::<isize>());
+        let target = match nearest_target {
+            Some(&target) => target,
+            None => {
+                *cached = None;
+                continue;
+            }
+        };
+        if cached.as_ref().map_or(true, |&(c, t, _)| (c, t) != (cell, target)) {
+            *cached = Some((cell, target, maze_map.maze.find_path(cell, target)));
+        }
+        let path = cached.as_ref().and_then(|&(_, _, ref path)| path.as_ref());
+        for step in path.iter().flat_map(|path| path.windows(2)) {
+            paths.push(::graphics::LineVertex::new(&maze_map.position(&step[0])));
+            paths.push(::graphics::LineVertex::new(&maze_map.position(&step[1])));
+        }
+    }
+
+    vec![
+        (contacts, CONTACT_COLOR),
+        (proximities, PROXIMITY_COLOR),
+        (vectors, CLOSEST_PLAYER_COLOR),
+        (paths, PATH_COLOR),
+    ]
+}
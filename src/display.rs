@@ -0,0 +1,165 @@
+pub const RESOLUTIONS: [[u32; 2]; 8] = [
+    [800, 600],
+    [1024, 768],
+    [1280, 720],
+    [1366, 768],
+    [1600, 900],
+    [1920, 1080],
+    [2560, 1440],
+    [3840, 2160],
+];
+
+#[derive(Clone, Copy, PartialEq, Debug, EnumIterator)]
+pub enum WindowMode {
+    Windowed,
+    Borderless,
+    Fullscreen,
+}
+
+impl WindowMode {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            WindowMode::Windowed => "Windowed",
+            WindowMode::Borderless => "Borderless",
+            WindowMode::Fullscreen => "Fullscreen",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize, EnumIterator)]
+pub enum PresentMode {
+    Vsync,
+    Mailbox,
+    Immediate,
+}
+
+impl PresentMode {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            PresentMode::Vsync => "Vsync",
+            PresentMode::Mailbox => "Triple buffering",
+            PresentMode::Immediate => "Immediate",
+        }
+    }
+
+    /// Fifo is always supported so it is used if the mode is not
+    pub fn supported_or_fifo(&self, capabilities: &::vulkano::swapchain::Capabilities) -> ::vulkano::swapchain::PresentMode {
+        let mode = match *self {
+            PresentMode::Vsync => ::vulkano::swapchain::PresentMode::Fifo,
+            PresentMode::Mailbox => ::vulkano::swapchain::PresentMode::Mailbox,
+            PresentMode::Immediate => ::vulkano::swapchain::PresentMode::Immediate,
+        };
+        if capabilities.present_modes.supports(mode) {
+            mode
+        } else {
+            ::vulkano::swapchain::PresentMode::Fifo
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Display {
+    /// Name of the monitor, primary monitor if none or not connected
+    pub monitor: Option<String>,
+    /// Inner size of the window in windowed mode
+    pub resolution: [u32; 2],
+    pub present_mode: PresentMode,
+}
+
+impl Default for Display {
+    fn default() -> Self {
+        Display {
+            monitor: None,
+            resolution: [1280, 720],
+            present_mode: PresentMode::Vsync,
+        }
+    }
+}
+
+/// Names and dimensions of the monitors connected at startup
+#[derive(Deref, DerefMut)]
+pub struct Monitors(pub Vec<(String, [u32; 2])>);
+
+impl Monitors {
+    pub fn new(events_loop: &::winit::EventsLoop) -> Self {
+        Monitors(
+            monitors(events_loop)
+                .into_iter()
+                .map(|(name, monitor)| {
+                    let (width, height) = monitor.get_dimensions();
+                    (name, [width, height])
+                })
+                .collect(),
+        )
+    }
+}
+
+fn monitors(events_loop: &::winit::EventsLoop) -> Vec<(String, ::winit::MonitorId)> {
+    events_loop.get_available_monitors()
+        .enumerate()
+        .map(|(i, monitor)| {
+            let name = monitor.get_name().unwrap_or_else(|| format!("Monitor {}", i + 1));
+            (name, monitor)
+        })
+        .collect()
+}
+
+fn find_monitor(events_loop: &::winit::EventsLoop, name: &Option<String>) -> ::winit::MonitorId {
+    name.as_ref()
+        .and_then(|name| {
+            monitors(events_loop)
+                .into_iter()
+                .find(|&(ref monitor_name, _)| monitor_name == name)
+                .map(|(_, monitor)| monitor)
+        })
+        .unwrap_or_else(|| events_loop.get_primary_monitor())
+}
+
+pub fn window_builder(
+    events_loop: &::winit::EventsLoop,
+    mode: WindowMode,
+    display: &Display,
+) -> ::winit::WindowBuilder {
+    let monitor = find_monitor(events_loop, &display.monitor);
+    let builder = ::winit::WindowBuilder::new();
+    match mode {
+        WindowMode::Windowed => builder
+            .with_dimensions(display.resolution[0], display.resolution[1]),
+        WindowMode::Borderless => {
+            let (width, height) = monitor.get_dimensions();
+            builder
+                .with_decorations(false)
+                .with_dimensions(width, height)
+        }
+        WindowMode::Fullscreen => builder.with_fullscreen(Some(monitor)),
+    }
+}
+
+/// Swapchain must be recreated afterward
+pub fn apply(
+    window: &::winit::Window,
+    events_loop: &::winit::EventsLoop,
+    mode: WindowMode,
+    display: &Display,
+) {
+    let monitor = find_monitor(events_loop, &display.monitor);
+    let (x, y) = monitor.get_position();
+    match mode {
+        WindowMode::Windowed => {
+            window.set_fullscreen(None);
+            window.set_decorations(true);
+            window.set_inner_size(display.resolution[0], display.resolution[1]);
+            window.set_position(x, y);
+        }
+        WindowMode::Borderless => {
+            let (width, height) = monitor.get_dimensions();
+            window.set_fullscreen(None);
+            window.set_decorations(false);
+            window.set_inner_size(width, height);
+            window.set_position(x, y);
+        }
+        WindowMode::Fullscreen => {
+            window.set_fullscreen(Some(monitor));
+        }
+    }
+}
@@ -2,6 +2,7 @@ use alga::linear::AffineTransformation;
 use specs::Builder;
 
 #[repr(usize)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Group {
     Target,
     Wall,
@@ -10,6 +11,15 @@ pub enum Group {
     Mine,
 }
 
+impl Group {
+    /// First group the collision groups are member of
+    pub fn of(groups: &::ncollide::world::CollisionGroups) -> Option<Group> {
+        [Group::Target, Group::Wall, Group::Player, Group::Rocket, Group::Mine].iter()
+            .cloned()
+            .find(|&group| groups.is_member_of(group as usize))
+    }
+}
+
 pub fn create_wall(pos: ::na::Vector3<f32>, _color: usize, world: &mut ::specs::World) {
     let shape = ::ncollide::shape::Cuboid3::new(::na::Vector3::from_element(0.5));
     let mut body = ::nphysics::object::RigidBody::new_static(shape, 0.0, 0.0);
@@ -26,11 +36,14 @@ pub fn create_wall(pos: ::na::Vector3<f32>, _color: usize, world: &mut ::specs::
         body,
         &mut world.write_storage(),
         &mut world.write_resource(),
+        &mut world.write_resource(),
     );
 }
 
-pub fn create_player(pos: ::na::Vector3<f32>, world: &::specs::World) {
-    let shape = ::ncollide::shape::Ball::new(::CFG.ball_radius);
+/// The entity is set as the one of the player slot
+pub fn create_player(player: usize, pos: ::na::Vector3<f32>, world: &::specs::World) -> ::specs::Entity {
+    let radius = world.read_resource::<::configuration::Configuration>().ball_radius;
+    let shape = ::ncollide::shape::Ball::new(radius);
     let mut group = ::nphysics::object::RigidBodyCollisionGroups::new_dynamic();
     group.set_membership(&[Group::Player as usize]);
 
@@ -40,25 +53,20 @@ pub fn create_player(pos: ::na::Vector3<f32>, world: &::specs::World) {
 
     let entity = world.entities().create();
     world.write_storage().insert(entity, ::component::Player).unwrap();
-    world.write_storage().insert(entity, ::component::FlightControl {
-        x_direction: 0.0,
-        y_direction: 0.0,
-        power: 0.0,
-        ang_damping: ::CFG.flight_control_ang_damping,
-        lin_damping: ::CFG.flight_control_lin_damping,
-        power_force: ::CFG.flight_control_power_force,
-        direction_force: ::CFG.flight_control_direction_force,
-        default_power_force: ::CFG.flight_control_default_power_force,
-    }).unwrap();
+    let flight_control = ::component::FlightControl::new(&world.read_resource());
+    world.write_storage().insert(entity, flight_control).unwrap();
+    world.write_storage().insert(entity, ::component::Trail::new()).unwrap();
 
     ::component::PhysicBody::add(
         entity,
         body,
         &mut world.write_storage(),
         &mut world.write_resource(),
+        &mut world.write_resource(),
     );
 
-    world.write_resource::<::resource::PlayersEntities>()[0] = Some(entity);
+    world.write_resource::<::resource::PlayersEntities>()[player] = Some(entity);
+    entity
 }
 
 pub fn create_tube(tube: &::tube::Tube, world: &mut ::specs::World) {
@@ -105,14 +113,16 @@ pub fn create_tube(tube: &::tube::Tube, world: &mut ::specs::World) {
             body,
             &mut world.write_storage(),
             &mut world.write_resource(),
+            &mut world.write_resource(),
         );
     }
 }
 
-pub fn create_rocket(pos: ::na::Isometry3<f32>, world: &::specs::World) {
+pub fn create_rocket(pos: ::na::Isometry3<f32>, world: &::specs::World) -> ::specs::Entity {
     let mut group = ::nphysics::object::RigidBodyCollisionGroups::new_dynamic();
     group.set_membership(&[Group::Rocket as usize]);
-    let shape = ::ncollide::shape::Ball::new(::CFG.ball_radius);
+    let radius = world.read_resource::<::configuration::Configuration>().ball_radius;
+    let shape = ::ncollide::shape::Ball::new(radius);
     let mut body = ::nphysics::object::RigidBody::new_dynamic(shape, 1.0, 0.0, 0.0);
     body.set_collision_groups(group);
     body.set_transformation(pos);
@@ -121,6 +131,7 @@ pub fn create_rocket(pos: ::na::Isometry3<f32>, world: &::specs::World) {
     world.write_storage().insert(entity, ::component::PlayerKiller).unwrap();
     world.write_storage().insert(entity, ::component::Contactor::new()).unwrap();
     world.write_storage().insert(entity, ::component::RocketControl).unwrap();
+    world.write_storage().insert(entity, ::component::Trail::new()).unwrap();
     world.write_storage().insert(entity, ::component::ClosestPlayer::new()).unwrap();
 
     ::component::PhysicBody::add(
@@ -128,19 +139,23 @@ pub fn create_rocket(pos: ::na::Isometry3<f32>, world: &::specs::World) {
         body,
         &mut world.write_storage(),
         &mut world.write_resource(),
+        &mut world.write_resource(),
     );
+    entity
 }
 
-pub fn create_rocket_launcher(pos: ::na::Isometry3<f32>, world: &mut ::specs::World) {
+/// The timer is the time left before the next launch
+pub fn create_rocket_launcher(pos: ::na::Isometry3<f32>, timer: f32, world: &mut ::specs::World) {
     world.create_entity()
-        .with(::component::RocketLauncher::new(pos))
+        .with(::component::RocketLauncher::new(pos, timer))
         .build();
 }
 
-pub fn create_mine(pos: ::na::Vector3<f32>, world: &::specs::World) {
+pub fn create_mine(pos: ::na::Vector3<f32>, world: &::specs::World) -> ::specs::Entity {
     let mut group = ::nphysics::object::RigidBodyCollisionGroups::new_dynamic();
     group.set_membership(&[Group::Mine as usize]);
-    let shape = ::ncollide::shape::Ball::new(::CFG.ball_radius);
+    let radius = world.read_resource::<::configuration::Configuration>().ball_radius;
+    let shape = ::ncollide::shape::Ball::new(radius);
     let mut body = ::nphysics::object::RigidBody::new_dynamic(shape, 1.0, 0.0, 0.0);
     body.set_collision_groups(group);
     body.set_transformation(::na::Isometry3::new(pos, ::na::zero()));
@@ -149,6 +164,7 @@ pub fn create_mine(pos: ::na::Vector3<f32>, world: &::specs::World) {
     world.write_storage().insert(entity, ::component::PlayerKiller).unwrap();
     world.write_storage().insert(entity, ::component::Contactor::new()).unwrap();
     world.write_storage().insert(entity, ::component::MineControl).unwrap();
+    world.write_storage().insert(entity, ::component::Trail::new()).unwrap();
     world.write_storage().insert(entity, ::component::ClosestPlayer::new()).unwrap();
 
     ::component::PhysicBody::add(
@@ -156,15 +172,18 @@ pub fn create_mine(pos: ::na::Vector3<f32>, world: &::specs::World) {
         body,
         &mut world.write_storage(),
         &mut world.write_resource(),
+        &mut world.write_resource(),
     );
+    entity
 }
 
-pub fn create_target(pos: ::na::Vector3<f32>, world: &mut ::specs::World) {
+pub fn create_target(pos: ::na::Vector3<f32>, world: &mut ::specs::World) -> ::specs::Entity {
     let mut group = ::nphysics::object::SensorCollisionGroups::new();
     group.set_membership(&[Group::Target as usize]);
     group.set_whitelist(&[Group::Target as usize]);
 
-    let shape = ::ncollide::shape::Ball::new(::CFG.ball_radius);
+    let radius = world.read_resource::<::configuration::Configuration>().ball_radius;
+    let shape = ::ncollide::shape::Ball::new(radius);
     let mut sensor = ::nphysics::object::Sensor::new(shape, None);
     sensor.set_relative_position(::na::Isometry3::new(pos, ::na::zero()));
     sensor.set_collision_groups(group);
@@ -179,5 +198,7 @@ pub fn create_target(pos: ::na::Vector3<f32>, world: &mut ::specs::World) {
         sensor,
         &mut world.write_storage(),
         &mut world.write_resource(),
+        &mut world.write_resource(),
     );
+    entity
 }
@@ -0,0 +1,38 @@
+/// Something that happened during an update, positions are in world coordinates
+#[derive(Clone, Copy, Debug)]
+pub enum Event {
+    PlayerSpawned {
+        player: ::specs::Entity,
+        position: ::na::Vector3<f32>,
+    },
+    PlayerKilled {
+        player: ::specs::Entity,
+        position: ::na::Vector3<f32>,
+    },
+    /// A player killer touched something and is destroyed
+    Explosion {
+        position: ::na::Vector3<f32>,
+    },
+    TargetCaptured {
+        position: ::na::Vector3<f32>,
+    },
+    RocketLaunched {
+        position: ::na::Isometry3<f32>,
+    },
+    /// A dynamic body started to touch a wall
+    WallHit {
+        entity: ::specs::Entity,
+        position: ::na::Vector3<f32>,
+    },
+}
+
+/// Events of the current update step.
+///
+/// Systems push to it and read it during the dispatch, it is cleared before each
+/// dispatch. Events pushed by lazy updates are only visible after maintain, in FrameEvents.
+#[derive(Default, Deref, DerefMut)]
+pub struct Events(pub Vec<Event>);
+
+/// Events of all the update steps of the current frame, for consumers running once per frame
+#[derive(Default, Deref, DerefMut)]
+pub struct FrameEvents(pub Vec<Event>);
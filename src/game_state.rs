@@ -23,76 +23,63 @@ pub trait GameState {
     fn paused(&self, world: &World) -> bool;
 }
 
-pub struct ValidateBuildedController {
-    controller: Vec<::winit::VirtualKeyCode>,
-    menu: ::menu::Menu<bool>,
-    stacked_state: Box<GameState>,
-}
+/// A paused screen made of one menu controlled by all players
+pub trait MenuState: 'static {
+    type Action: Clone;
 
-impl ValidateBuildedController {
-    fn new(controller: Vec<::winit::VirtualKeyCode>, stacked_state: Box<GameState>) -> Self {
-        let menu = ::menu::MenuBuilder::new()
-            .add_middle("OK".to_string(), true)
-            .add_middle("Cancel".to_string(), false)
-            .build();
+    fn menu(&self) -> &::menu::Menu<Self::Action>;
+    fn menu_mut(&mut self) -> &mut ::menu::Menu<Self::Action>;
+    fn process_action(self: Box<Self>, action: Self::Action, world: &mut World) -> Box<GameState>;
 
-        ValidateBuildedController {
-            controller,
-            menu,
-            stacked_state,
-        }
+    /// Lines displayed above the menu, aligned on '↔'
+    fn description(&self, _world: &World) -> Vec<String> {
+        vec![]
     }
 
-    fn process_action(self: Box<Self>, action: bool, world: &mut World) -> Box<GameState> {
-        match action {
-            true => {
-                let mut players_controllers = world.write_resource::<::resource::PlayersControllers>();
-                let mode = world.read_resource::<::resource::Mode>();
-                let free_player = players_controllers
-                    .iter()
-                    .take(mode.number_of_player())
-                    .enumerate()
-                    .find(|&(_, player_gamepad_id)| player_gamepad_id.is_none())
-                    .map(|(player_number, _)| player_number);
-
-                if let Some(free_player) = free_player {
-                    players_controllers[free_player] = Some(::resource::Controller::new_keyboard(&self.controller));
-                }
-            },
-            false => (),
-        }
-        self.stacked_state
+    /// Called before the menu is drawn
+    fn update(&mut self, _world: &World) {}
+
+    /// Return whether the event has been consumed
+    fn winit_hook(&mut self, _event: &::winit::Event, _world: &mut World) -> bool {
+        false
     }
-}
 
-impl GameState for ValidateBuildedController {
-    fn update_draw_ui(self: Box<Self>, world: &mut World) -> Box<GameState> {
-        let mut text = world.write_resource::<::resource::Text>();
-        let font = world.read_resource::<::resource::Font>();
-        let joystick_description = ::resource::Control::iter_variants()
-            .zip(self.controller.iter())
-            .map(|(control, key)| { format!("{} ↔ {:?}", control, key) })
-            .collect::<Vec<_>>();
+    /// Return whether the event has been consumed
+    fn gilrs_hook(&mut self, _id: usize, _event: ::gilrs::EventType, _world: &mut World) -> bool {
+        false
+    }
 
-        text.global = ::util::joystick_description_layout(joystick_description, '↔', self.menu.len(), &font);
-        text.global.extend(self.menu.glyphs(&font));
+    /// Controllers whose keyboard mappings navigate the menu
+    fn controllers(&self, world: &World) -> ::resource::PlayersControllers {
+        ::resource::PlayersControllers(world.read_resource::<::resource::PlayersControllers>().0.clone())
+    }
+}
 
+impl<T: MenuState> GameState for T {
+    fn update_draw_ui(mut self: Box<Self>, world: &mut World) -> Box<GameState> {
+        self.update(world);
+        {
+            let mut text = world.write_resource::<::resource::Text>();
+            let font = world.read_resource::<::resource::Font>();
+            let text_scale = world.read_resource::<::configuration::Configuration>().text_scale;
+            let description = self.description(world);
+
+            text.global = if description.is_empty() {
+                vec![]
+            } else {
+                ::util::joystick_description_layout(description, '↔', self.menu().len(), &font, text_scale)
+            };
+            text.global.extend(self.menu().glyphs(&font, text_scale));
+        }
         self
     }
 
     fn winit_event(mut self: Box<Self>, event: ::winit::Event, world: &mut World) -> Box<GameState> {
-        let action = {
-            let controllers = world.read_resource::<::resource::PlayersControllers>();
-            let mut possible_next_controllers = controllers.clone();
-            if let Some(c) = possible_next_controllers.iter_mut().find(|c| c.is_none()) {
-                *c = Some(::resource::Controller::new_keyboard(&self.controller));
-            } else {
-                // No available controller
-                return self.stacked_state;
-            }
-            self.menu.winit_event(event, None, &::resource::PlayersControllers(possible_next_controllers))
-        };
-        if let Some(action) = action {
+        if self.winit_hook(&event, world) {
+            return self;
+        }
+        let controllers = self.controllers(world);
+        if let Some(action) = self.menu_mut().winit_event(event, None, &controllers) {
             self.process_action(action, world)
         } else {
             self
@@ -101,11 +88,14 @@ impl GameState for ValidateBuildedController {
 
     fn gilrs_event(
         mut self: Box<Self>,
-        _id: usize,
+        id: usize,
         event: ::gilrs::EventType,
         world: &mut World,
     ) -> Box<GameState> {
-        if let Some(action) = self.menu.gilrs_event(event) {
+        if self.gilrs_hook(id, event, world) {
+            return self;
+        }
+        if let Some(action) = self.menu_mut().gilrs_event(event) {
             self.process_action(action, world)
         } else {
             self
@@ -127,32 +117,83 @@ impl GameState for ValidateBuildedController {
 }
 
 // TODO: enforce not already used key
+/// Bind a key to each control then join with the new keyboard
 pub struct BuildController {
-    current_input: ::resource::Control,
     controller: Vec<::winit::VirtualKeyCode>,
+    menu: ::menu::Menu<BuildControllerAction>,
     stacked_state: Box<GameState>,
 }
 
 impl BuildController {
     pub fn new(stacked_state: Box<GameState>) -> Self {
         BuildController {
-            current_input: ::resource::Control::iter_variants().next().unwrap(),
             controller: vec![],
+            menu: BuildController::create_menu(false),
             stacked_state,
         }
     }
+
+    fn create_menu(complete: bool) -> ::menu::Menu<BuildControllerAction> {
+        use self::BuildControllerAction::*;
+        let mut menu = ::menu::MenuBuilder::new();
+        if complete {
+            menu = menu.add_middle("OK".to_string(), Validate);
+        }
+        menu.add_middle("Cancel".to_string(), Cancel).build()
+    }
+
+    fn is_complete(&self) -> bool {
+        self.controller.len() == ::resource::Control::iter_variants().count()
+    }
 }
 
-impl GameState for BuildController {
-    fn update_draw_ui(self: Box<Self>, world: &mut World) -> Box<GameState> {
-        let mut text = world.write_resource::<::resource::Text>();
-        let font = world.read_resource::<::resource::Font>();
-        text.global = ::util::menu_layout(vec![format!("Press key for {}", self.current_input)], None, &font);
-        self
+#[derive(Clone, Copy)]
+pub enum BuildControllerAction {
+    Validate,
+    Cancel,
+}
+
+impl MenuState for BuildController {
+    type Action = BuildControllerAction;
+
+    fn menu(&self) -> &::menu::Menu<BuildControllerAction> {
+        &self.menu
     }
 
-    fn winit_event(mut self: Box<Self>, event: ::winit::Event, _world: &mut World) -> Box<GameState> {
-        match event {
+    fn menu_mut(&mut self) -> &mut ::menu::Menu<BuildControllerAction> {
+        &mut self.menu
+    }
+
+    fn process_action(self: Box<Self>, action: BuildControllerAction, world: &mut World) -> Box<GameState> {
+        if let BuildControllerAction::Validate = action {
+            world.join_player(::resource::Controller::new_keyboard(&self.controller));
+        }
+        self.stacked_state
+    }
+
+    fn description(&self, world: &World) -> Vec<String> {
+        let mut description = ::resource::Control::iter_variants()
+            .take(self.controller.len() + 1)
+            .enumerate()
+            .map(|(i, control)| match self.controller.get(i) {
+                Some(key) => format!("{} ↔ {:?}", control, key),
+                None => format!("{} ↔ [press a key]", control),
+            })
+            .collect::<Vec<_>>();
+
+        let controllers = world.read_resource::<::resource::PlayersControllers>();
+        let number_of_player = world.read_resource::<::resource::Mode>().number_of_player();
+        if self.is_complete() && controllers.iter().take(number_of_player).all(|c| c.is_some()) {
+            description.push("Note ↔ no free slot to join".to_string());
+        }
+        description
+    }
+
+    fn winit_hook(&mut self, event: &::winit::Event, _world: &mut World) -> bool {
+        if self.is_complete() {
+            return false;
+        }
+        match *event {
             ::winit::Event::WindowEvent {
                 event:
                     ::winit::WindowEvent::KeyboardInput {
@@ -167,41 +208,151 @@ impl GameState for BuildController {
                 ..
             } => {
                 self.controller.push(virtual_keycode);
-                if let Some(control) =  ::resource::Control::iter_variants().skip_while(|v| *v != self.current_input).skip(1).next() {
-                    Box::new(BuildController {
-                        current_input: control,
-                        controller: self.controller.clone(),
-                        stacked_state: self.stacked_state,
-                    }) as Box<_>
-                } else {
-                    Box::new(ValidateBuildedController::new(self.controller.clone(), self.stacked_state)) as Box<_>
+                if self.is_complete() {
+                    self.menu = BuildController::create_menu(true);
                 }
+                true
             }
-            _ => self,
+            _ => false,
         }
     }
 
-    fn gilrs_event(
-        self: Box<Self>,
-        _id: usize,
-        _event: ::gilrs::EventType,
-        _world: &mut World,
-    ) -> Box<GameState> {
-        println!("{:?}", _event);
-        self
+    /// The new keyboard can already navigate the menu
+    fn controllers(&self, world: &World) -> ::resource::PlayersControllers {
+        let mut controllers = world.read_resource::<::resource::PlayersControllers>().0.clone();
+        if self.is_complete() {
+            if let Some(c) = controllers.iter_mut().find(|c| c.is_none()) {
+                *c = Some(::resource::Controller::new_keyboard(&self.controller));
+            }
+        }
+        ::resource::PlayersControllers(controllers)
     }
+}
 
-    fn gilrs_gamepad_state(
-        self: Box<Self>,
-        _id: usize,
-        _gamepad: &::gilrs::Gamepad,
-        _world: &mut World,
-    ) -> Box<GameState> {
+pub struct Lobby {
+    menu: ::menu::Menu<LobbyAction>,
+    stacked_state: Box<GameState>,
+}
+
+impl Lobby {
+    pub fn new(world: &World, stacked_state: Box<GameState>) -> Self {
+        use self::LobbyAction::*;
+
+        let number_of_player = world.read_resource::<::resource::Mode>().number_of_player();
+        let mut menu = ::menu::MenuBuilder::new();
+        for player in 0..number_of_player {
+            menu = menu.add_left_right(String::new(), Swap(player), Leave(player));
+        }
+
+        let mut names = ::menu::MenuBuilder::new();
+        {
+            let save = world.read_resource::<::resource::Save>();
+            for player in 0..number_of_player {
+                let action: fn(String) -> LobbyAction = match player {
+                    0 => |name| SetName(0, name),
+                    1 => |name| SetName(1, name),
+                    _ => |name| SetName(2, name),
+                };
+                names = names.add_text_entry(format!("Player {}", player + 1), save.player_name(player), action);
+            }
+        }
+
+        let menu = menu
+            .add_submenu("Names".to_string(), names)
+            .add_middle("Add keyboard".to_string(), AddKeyboard)
+            .add_middle("Back".to_string(), Back)
+            .build();
+
+        let mut lobby = Lobby {
+            menu,
+            stacked_state,
+        };
+        lobby.reset_names(world);
+        lobby
+    }
+
+    fn reset_names(&mut self, world: &World) {
+        let players_controllers = world.read_resource::<::resource::PlayersControllers>();
+        let disconnected_gamepads = world.read_resource::<::resource::DisconnectedGamepads>();
+        let mode = world.read_resource::<::resource::Mode>();
+        let save = world.read_resource::<::resource::Save>();
+
+        for player in 0..mode.number_of_player() {
+            let controller = match (&players_controllers[player], disconnected_gamepads[player]) {
+                (&Some(ref controller), _) => format!("{}", controller),
+                (&None, Some(id)) => format!("Gamepad {} (disconnected)", id),
+                (&None, None) => "Free".to_string(),
+            };
+            self.menu.reset_name(player, format!("{}: {}", save.player_name(player), controller));
+        }
+    }
+}
+
+#[derive(Clone)]
+pub enum LobbyAction {
+    Swap(usize),
+    Leave(usize),
+    SetName(usize, String),
+    AddKeyboard,
+    Back,
+}
+
+impl MenuState for Lobby {
+    type Action = LobbyAction;
+
+    fn menu(&self) -> &::menu::Menu<LobbyAction> {
+        &self.menu
+    }
+
+    fn menu_mut(&mut self) -> &mut ::menu::Menu<LobbyAction> {
+        &mut self.menu
+    }
+
+    fn process_action(mut self: Box<Self>, action: LobbyAction, world: &mut World) -> Box<GameState> {
+        use self::LobbyAction::*;
+        match action {
+            Swap(player) => {
+                let number_of_player = world.read_resource::<::resource::Mode>().number_of_player();
+                world.swap_players(player, (player + 1) % number_of_player);
+            }
+            Leave(player) => world.leave_player(player),
+            SetName(player, name) => {
+                world.write_resource::<::resource::Save>().set_player_name_lazy(player, &name);
+            }
+            AddKeyboard => return Box::new(BuildController::new(self)) as Box<_>,
+            Back => return self.stacked_state,
+        }
+        self.reset_names(world);
         self
     }
 
-    fn paused(&self, _world: &World) -> bool {
-        true
+    fn description(&self, _world: &World) -> Vec<String> {
+        vec![
+            "Join ↔ [START]".to_string(),
+            "Leave ↔ [SELECT] or [→]".to_string(),
+            "Swap with next ↔ [←]".to_string(),
+        ]
+    }
+
+    fn update(&mut self, world: &World) {
+        self.reset_names(world);
+    }
+
+    fn gilrs_hook(&mut self, id: usize, event: ::gilrs::EventType, world: &mut World) -> bool {
+        match event {
+            EventType::ButtonPressed(Button::Start, _) => {
+                world.join_player(::resource::Controller::Gamepad(id));
+                true
+            }
+            EventType::ButtonPressed(Button::Select, _) => {
+                let player = world.read_resource::<::resource::PlayersControllers>().gamepad_player(id);
+                if let Some(player) = player {
+                    world.leave_player(player);
+                }
+                true
+            }
+            _ => false,
+        }
     }
 }
 
@@ -211,21 +362,37 @@ pub struct Game {
 }
 
 impl Game {
-    pub fn new() -> Self {
+    pub fn new(world: &World) -> Self {
+        world.write_resource::<::snapshot::Playing>().0 = true;
         Game {
             players_menus: [
-                Some(Game::create_menu()),
-                Some(Game::create_menu()),
-                Some(Game::create_menu()),
+                Some(Game::create_menu(world)),
+                Some(Game::create_menu(world)),
+                Some(Game::create_menu(world)),
             ],
             space_return: [false; 2],
         }
     }
 
-    fn create_menu() -> ::menu::Menu<GameMenuAction> {
+    fn create_menu(world: &World) -> ::menu::Menu<GameMenuAction> {
         use self::GameMenuAction::*;
+        let time_control = world.read_resource::<::resource::TimeControl>();
         ::menu::MenuBuilder::new()
             .add_middle("Resume".to_string(), Resume)
+            .add_submenu("Time".to_string(), ::menu::MenuBuilder::new()
+                .add_slider(
+                    "Speed".to_string(),
+                    time_control.scale,
+                    ::resource::MIN_TIME_SCALE,
+                    ::resource::MAX_TIME_SCALE,
+                    ::resource::MIN_TIME_SCALE,
+                    SetTimeScale,
+                )
+                .add_toggle("Frozen".to_string(), time_control.frozen, SetFrozen)
+                .add_middle("Single step".to_string(), SingleStep)
+            )
+            .add_middle("Controllers".to_string(), Controllers)
+            .add_middle("Leave".to_string(), Leave)
             .add_middle("Main".to_string(), MainMenu)
             .build()
     }
@@ -234,24 +401,46 @@ impl Game {
         use self::GameMenuAction::*;
         match action {
             Resume => self.players_menus[player] = None,
-            MainMenu => return Box::new(GlobalMenu::new(world)) as Box<_>,
+            SetTimeScale(scale) => world.write_resource::<::resource::TimeControl>().set_scale(scale),
+            SetFrozen(frozen) => world.write_resource::<::resource::TimeControl>().frozen = frozen,
+            SingleStep => world.write_resource::<::resource::TimeControl>().single_step(),
+            Controllers => {
+                self.players_menus[player] = None;
+                return Box::new(Lobby::new(world, self)) as Box<_>;
+            }
+            Leave => {
+                self.players_menus[player] = Some(Game::create_menu(world));
+                world.leave_player(player);
+            }
+            MainMenu => {
+                ::snapshot::save_playing(world);
+                world.write_resource::<::snapshot::Playing>().0 = false;
+                return Box::new(GlobalMenu::new(world)) as Box<_>;
+            }
         }
         self
     }
 }
 
 // TODO: add tolerance
-//       add disconnect device
 #[derive(Clone, Copy)]
 enum GameMenuAction {
     Resume,
+    SetTimeScale(f32),
+    SetFrozen(bool),
+    SingleStep,
+    Controllers,
+    Leave,
     MainMenu,
 }
 
 impl GameState for Game {
     fn update_draw_ui(self: Box<Self>, world: &mut World) -> Box<GameState> {
+        ::snapshot::complete_level(world);
+
         let mut text = world.write_resource::<::resource::Text>();
         let font = world.read_resource::<::resource::Font>();
+        let text_scale = world.read_resource::<::configuration::Configuration>().text_scale;
         let mode = world.read_resource::<::resource::Mode>();
         let players_controllers = world.read_resource::<::resource::PlayersControllers>();
 
@@ -266,13 +455,15 @@ impl GameState for Game {
                     "Press [START] to join with a gamepad".to_string(),
                     "Press [SPACE]+[RETURN] to configure a keyboard".to_string(),
                 ];
-                text.players[player] = ::util::menu_layout(lines, None, &font);
+                text.players[player] = ::util::menu_layout(lines, None, &font, text_scale);
             } else if let &Some(ref menu) = menu {
-                text.players[player] = menu.glyphs(&font);
+                text.players[player] = menu.glyphs(&font, text_scale);
             } else if number_of_player != number_of_controllers {
-                text.players[player] = ::util::menu_layout(vec!["Waiting for other players".to_string()], None, &font);
+                text.players[player] = ::util::menu_layout(vec!["Waiting for other players".to_string()], None, &font, text_scale);
             } else {
                 text.players[player] = vec![];
+                let lines = ::hud::lines(world, player);
+                text.huds[player] = ::util::notification_layout(lines, &font, text_scale * ::hud::TEXT_SCALE);
             }
         }
 
@@ -332,7 +523,7 @@ impl GameState for Game {
                 if self.players_menus[player].is_some() {
                     self.players_menus[player] = None;
                 } else {
-                    self.players_menus[player] = Some(Game::create_menu());
+                    self.players_menus[player] = Some(Game::create_menu(world));
                 }
             }
             self
@@ -390,48 +581,21 @@ impl GameState for Game {
         event: ::gilrs::EventType,
         world: &mut World,
     ) -> Box<GameState> {
-        // TODO: Menu actions
-        // Game actions
-        let mut players_controllers = world.write_resource::<::resource::PlayersControllers>();
-
-        match event {
-            EventType::Disconnected => {
-                for player_controller in players_controllers.iter_mut() {
-                    if let Some(::resource::Controller::Gamepad(player_controller_id)) = *player_controller {
-                        if player_controller_id == id {
-                            *player_controller = None;
-                        }
-                    }
-                }
-                return self;
+        // The slot has already been released by the main loop
+        if let EventType::Disconnected = event {
+            if world.read_resource::<::resource::DisconnectedGamepads>().contains(&Some(id)) {
+                return Box::new(Lobby::new(world, self)) as Box<_>;
             }
-            _ => (),
+            return self;
         }
 
-        let mode = world.read_resource::<::resource::Mode>();
-        let players_entities = world.read_resource::<::resource::PlayersEntities>();
-        let mut flight_controls = world.write_storage::<::component::FlightControl>();
-
-        let player = players_controllers
-            .iter()
-            .enumerate()
-            .find(|&(_, player_controller)| {
-                if let Some(::resource::Controller::Gamepad(player_controller_id)) = *player_controller {
-                    player_controller_id == id
-                } else {
-                    false
-                }
-            })
-            .map(|(player_number, _)| player_number);
-
-        let free_player = players_controllers
-            .iter()
-            .take(mode.number_of_player())
-            .enumerate()
-            .find(|&(_, player_gamepad_id)| player_gamepad_id.is_none())
-            .map(|(player_number, _)| player_number);
+        // TODO: Menu actions
+        // Game actions
+        let player = world.read_resource::<::resource::PlayersControllers>().gamepad_player(id);
 
         if let Some(player) = player {
+            let players_entities = world.read_resource::<::resource::PlayersEntities>();
+            let mut flight_controls = world.write_storage::<::component::FlightControl>();
             let flight_control =
                 players_entities[player].and_then(|entity| flight_controls.get_mut(entity));
 
@@ -452,10 +616,10 @@ impl GameState for Game {
                     _ => (),
                 }
             }
-        } else if let Some(free_player) = free_player {
+        } else {
             match event {
                 EventType::ButtonPressed(Button::Start, _) => {
-                    players_controllers[free_player] = Some(::resource::Controller::Gamepad(id));
+                    world.join_player(::resource::Controller::Gamepad(id));
                 }
                 _ => (),
             }
@@ -481,6 +645,8 @@ impl GameState for Game {
 
 pub struct GlobalMenu {
     menu: ::menu::Menu<GlobalMenuAction>,
+    /// Index of the entry showing the number of players
+    players_entry: usize,
 }
 
 impl GlobalMenu {
@@ -489,25 +655,73 @@ impl GlobalMenu {
 
         let players = world.read_resource::<::resource::Mode>().number_of_player();
 
-        let menu = ::menu::MenuBuilder::new()
+        let mut menu = ::menu::MenuBuilder::new();
+        let mut players_entry = 0;
+        // Hidden if the snapshot can't be located
+        if ::snapshot::Snapshot::exists().unwrap_or(false) {
+            menu = menu.add_middle("Continue".to_string(), Continue);
+            players_entry += 1;
+        }
+        let menu = menu
             .add_left_right(format!("Players: {}", players), ReducePlayers, IncreasePlayers)
+            .add_middle("Controllers".to_string(), Controllers)
             .add_middle("New map".to_string(), NewMap)
+            .add_middle("Display".to_string(), Display)
+            .add_middle("Settings".to_string(), Settings)
             .build();
 
         GlobalMenu {
             menu,
+            players_entry,
         }
     }
+}
+
+#[derive(Clone, Copy)]
+pub enum GlobalMenuAction {
+    Continue,
+    ReducePlayers,
+    IncreasePlayers,
+    Controllers,
+    NewMap,
+    Display,
+    Settings,
+}
+
+impl MenuState for GlobalMenu {
+    type Action = GlobalMenuAction;
+
+    fn menu(&self) -> &::menu::Menu<GlobalMenuAction> {
+        &self.menu
+    }
+
+    fn menu_mut(&mut self) -> &mut ::menu::Menu<GlobalMenuAction> {
+        &mut self.menu
+    }
 
     fn process_action(mut self: Box<Self>, action: GlobalMenuAction, world: &mut World) -> Box<GameState> {
         use self::GlobalMenuAction::*;
         match action {
+            Continue => match ::snapshot::Snapshot::load() {
+                Ok(snapshot) => {
+                    snapshot.restore(world);
+                    // Saved again when leaving the game
+                    if let Err(e) = ::snapshot::Snapshot::delete() {
+                        world.write_resource::<::resource::Notifications>().push(e);
+                    }
+                    Box::new(Game::new(world)) as Box<_>
+                }
+                Err(e) => {
+                    world.write_resource::<::resource::Notifications>().push(e);
+                    self
+                }
+            },
             ReducePlayers => {
                 {
                     let mut mode = world.write_resource::<::resource::Mode>();
                     mode.reduce();
                     let players = mode.number_of_player();
-                    self.menu.reset_name(0, format!("Players: {}", players));
+                    self.menu.reset_name(self.players_entry, format!("Players: {}", players));
                 }
                 world.reset_for_mode();
                 self
@@ -517,159 +731,444 @@ impl GlobalMenu {
                     let mut mode = world.write_resource::<::resource::Mode>();
                     mode.increase();
                     let players = mode.number_of_player();
-                    self.menu.reset_name(0, format!("Players: {}", players));
+                    self.menu.reset_name(self.players_entry, format!("Players: {}", players));
                 }
                 world.reset_for_mode();
                 self
             },
+            Controllers => Box::new(Lobby::new(world, self)) as Box<_>,
             NewMap => Box::new(NewMapMenu::new(world)) as Box<_>,
+            Display => Box::new(DisplayMenu::new(world, self)) as Box<_>,
+            Settings => Box::new(SettingsMenu::new(world, self)) as Box<_>,
         }
     }
 }
 
+struct DisplayMenu {
+    menu: ::menu::Menu<DisplayMenuAction>,
+    stacked_state: Box<GameState>,
+}
+
+impl DisplayMenu {
+    pub fn new(world: &World, stacked_state: Box<GameState>) -> Self {
+        DisplayMenu {
+            menu: DisplayMenu::create_menu(world),
+            stacked_state,
+        }
+    }
+
+    fn create_menu(world: &World) -> ::menu::Menu<DisplayMenuAction> {
+        use self::DisplayMenuAction::*;
+
+        let save = world.read_resource::<::resource::Save>();
+        let monitors = world.read_resource::<::display::Monitors>();
+        let display = save.display();
+
+        let modes = ::display::WindowMode::iter_variants()
+            .map(|mode| mode.name().to_string())
+            .collect::<Vec<_>>();
+        let mode = ::display::WindowMode::iter_variants()
+            .position(|mode| mode == save.window_mode())
+            .unwrap();
+
+        let mut monitor_names = monitors.iter()
+            .map(|&(ref name, _)| name.clone())
+            .collect::<Vec<_>>();
+        if monitor_names.is_empty() {
+            monitor_names.push("Primary".to_string());
+        }
+        let monitor = display.monitor.as_ref()
+            .and_then(|monitor| monitor_names.iter().position(|name| name == monitor))
+            .unwrap_or(0);
+
+        let resolutions = ::display::RESOLUTIONS.iter()
+            .map(|resolution| format!("{}x{}", resolution[0], resolution[1]))
+            .collect::<Vec<_>>();
+        let resolution = ::display::RESOLUTIONS.iter()
+            .position(|&resolution| resolution == display.resolution)
+            .unwrap_or(2);
+
+        let present_modes = ::display::PresentMode::iter_variants()
+            .map(|mode| mode.name().to_string())
+            .collect::<Vec<_>>();
+        let present_mode = ::display::PresentMode::iter_variants()
+            .position(|mode| mode == display.present_mode)
+            .unwrap();
+
+        let devices = world.read_resource::<::graphics::Devices>();
+        let device_names = devices.list.iter()
+            .map(|device| device.name.clone())
+            .collect::<Vec<_>>();
+        let saved_uuid = save.vulkan_device_uuid().unwrap_or(devices.current);
+        let device = devices.list.iter()
+            .position(|device| device.uuid == saved_uuid)
+            .unwrap_or(0);
+
+        ::menu::MenuBuilder::new()
+            .add_choice("Mode".to_string(), modes, mode, SetMode)
+            .add_choice("Monitor".to_string(), monitor_names, monitor, SetMonitor)
+            .add_choice("Resolution".to_string(), resolutions, resolution, SetResolution)
+            .add_choice("Present mode".to_string(), present_modes, present_mode, SetPresentMode)
+            .add_choice("Device".to_string(), device_names, device, SetDevice)
+            .add_middle("Back".to_string(), Back)
+            .build()
+    }
+}
+
 #[derive(Clone, Copy)]
-enum GlobalMenuAction {
-    ReducePlayers,
-    IncreasePlayers,
-    NewMap,
+pub enum DisplayMenuAction {
+    SetMode(usize),
+    SetMonitor(usize),
+    SetResolution(usize),
+    SetPresentMode(usize),
+    SetDevice(usize),
+    Back,
 }
 
-impl GameState for GlobalMenu {
-    fn update_draw_ui(self: Box<Self>, world: &mut World) -> Box<GameState> {
-        let mut text = world.write_resource::<::resource::Text>();
-        let font = world.read_resource::<::resource::Font>();
-        text.global = self.menu.glyphs(&font);
+impl MenuState for DisplayMenu {
+    type Action = DisplayMenuAction;
 
-        self
+    fn menu(&self) -> &::menu::Menu<DisplayMenuAction> {
+        &self.menu
     }
 
-    fn winit_event(mut self: Box<Self>, event: ::winit::Event, world: &mut World) -> Box<GameState> {
-        let action = {
-            let controllers = world.read_resource::<::resource::PlayersControllers>();
-            self.menu.winit_event(event, None, &controllers)
-        };
-        if let Some(action) = action {
-            self.process_action(action, world)
-        } else {
-            self
-        }
+    fn menu_mut(&mut self) -> &mut ::menu::Menu<DisplayMenuAction> {
+        &mut self.menu
     }
 
-    fn gilrs_event(
-        mut self: Box<Self>,
-        _id: usize,
-        event: ::gilrs::EventType,
-        world: &mut World,
-    ) -> Box<GameState> {
-        if let Some(action) = self.menu.gilrs_event(event) {
-            self.process_action(action, world)
-        } else {
-            self
+    fn process_action(mut self: Box<Self>, action: DisplayMenuAction, world: &mut World) -> Box<GameState> {
+        use self::DisplayMenuAction::*;
+
+        // Settings are saved here and applied by the main loop
+        {
+            let mut save = world.write_resource::<::resource::Save>();
+            let mut display = save.display().clone();
+            match action {
+                SetMode(index) => {
+                    if let Some(mode) = ::display::WindowMode::iter_variants().nth(index) {
+                        save.set_window_mode_lazy(mode);
+                    }
+                }
+                SetMonitor(index) => {
+                    let monitors = world.read_resource::<::display::Monitors>();
+                    display.monitor = monitors.get(index).map(|&(ref name, _)| name.clone());
+                }
+                SetResolution(index) => display.resolution = ::display::RESOLUTIONS[index],
+                SetPresentMode(index) => {
+                    if let Some(mode) = ::display::PresentMode::iter_variants().nth(index) {
+                        display.present_mode = mode;
+                    }
+                }
+                SetDevice(index) => {
+                    let devices = world.read_resource::<::graphics::Devices>();
+                    if let Some(device) = devices.list.get(index) {
+                        save.set_vulkan_device_uuid_lazy(&device.uuid);
+                    }
+                }
+                Back => (),
+            }
+            save.set_display_lazy(&display);
         }
-    }
 
-    fn gilrs_gamepad_state(
-        self: Box<Self>,
-        _id: usize,
-        _gamepad: &::gilrs::Gamepad,
-        _world: &mut World,
-    ) -> Box<GameState> {
-        self
+        match action {
+            Back => self.stacked_state,
+            _ => {
+                let menu = DisplayMenu::create_menu(world);
+                self.menu.refresh(menu);
+                self
+            }
+        }
     }
 
-    fn paused(&self, _world: &World) -> bool {
-        true
+    fn description(&self, world: &World) -> Vec<String> {
+        let save = world.read_resource::<::resource::Save>();
+        let devices = world.read_resource::<::graphics::Devices>();
+        let mut description = vec![];
+        if save.window_mode() != ::display::WindowMode::Windowed {
+            description.push("Note ↔ resolution only applies to windowed mode".to_string());
+        }
+        if devices.unavailable.is_some() && *save.vulkan_device_uuid() == devices.unavailable {
+            description.push("Note ↔ saved device not available".to_string());
+        } else if save.vulkan_device_uuid().map_or(false, |uuid| uuid != devices.current) {
+            description.push("Note ↔ device change applies at next start".to_string());
+        }
+        if let Some(device) = devices.list.iter().find(|device| device.uuid == devices.current) {
+            description.push(format!("Current device ↔ {}", device.description()));
+        }
+        description
     }
 }
 
 struct NewMapMenu {
     menu: ::menu::Menu<NewMapMenuAction>,
+    level_builder: ::level::LevelBuilder,
 }
 
 impl NewMapMenu {
-    pub fn new(_world: &::specs::World) -> Self {
+    pub fn new(world: &::specs::World) -> Self {
+        let level_builder = world.read_resource::<::resource::Save>().level_builder().clone();
+        NewMapMenu {
+            menu: NewMapMenu::create_menu(&level_builder),
+            level_builder,
+        }
+    }
+
+    fn create_menu(level: &::level::LevelBuilder) -> ::menu::Menu<NewMapMenuAction> {
         use self::NewMapMenuAction::*;
-        let menu = ::menu::MenuBuilder::new()
+
+        let mut presets = ::level::Preset::iter_variants()
+            .map(|preset| preset.name().to_string())
+            .collect::<Vec<_>>();
+        presets.push("Custom".to_string());
+        // Presets do not depend on the theme
+        let preset = ::level::Preset::iter_variants()
+            .position(|preset| ::level::LevelBuilder { theme: level.theme, ..preset.level_builder() } == *level)
+            .unwrap_or(presets.len() - 1);
+
+        let themes = ::texture::Theme::iter_variants()
+            .map(|theme| theme.name().to_string())
+            .collect::<Vec<_>>();
+        let theme = ::texture::Theme::iter_variants()
+            .position(|theme| theme == level.theme)
+            .unwrap();
+
+        ::menu::MenuBuilder::new()
+            .add_choice("Preset".to_string(), presets, preset, SetPreset)
+            .add_slider("Size".to_string(), (level.half_size * 2 + 1) as f32, 5.0, 61.0, 2.0, SetSize)
+            .add_slider("Density %".to_string(), level.percent as f32, 0.0, 100.0, 1.0, SetPercent)
+            .add_slider("Mines".to_string(), level.mine as f32, 0.0, 50.0, 1.0, SetMine)
+            .add_slider("Targets".to_string(), level.target as f32, 0.0, 50.0, 1.0, SetTarget)
+            .add_slider("Rocket launchers".to_string(), level.rocket_launcher as f32, 0.0, 50.0, 1.0, SetRocketLauncher)
+            .add_choice("Theme".to_string(), themes, theme, SetTheme)
+            .add_submenu("Advanced".to_string(), ::menu::MenuBuilder::new()
+                .add_toggle("X shift".to_string(), level.x_shift, SetXShift)
+                .add_toggle("Y shift".to_string(), level.y_shift, SetYShift)
+                .add_toggle("Z shift".to_string(), level.z_shift, SetZShift)
+                .add_slider("Tubes".to_string(), level.columns as f32, 0.0, 20.0, 1.0, SetColumns)
+                .add_slider("Unit".to_string(), level.unit, 0.1, 5.0, 0.1, SetUnit)
+            )
             .add_middle("Play".to_string(), Play)
-            .build();
+            .build()
+    }
+}
 
-        NewMapMenu {
-            menu,
-        }
+#[derive(Clone, Copy)]
+pub enum NewMapMenuAction {
+    SetPreset(usize),
+    SetSize(f32),
+    SetPercent(f32),
+    SetXShift(bool),
+    SetYShift(bool),
+    SetZShift(bool),
+    SetColumns(f32),
+    SetUnit(f32),
+    SetMine(f32),
+    SetTarget(f32),
+    SetRocketLauncher(f32),
+    SetTheme(usize),
+    Play,
+}
+
+impl MenuState for NewMapMenu {
+    type Action = NewMapMenuAction;
+
+    fn menu(&self) -> &::menu::Menu<NewMapMenuAction> {
+        &self.menu
     }
 
-    fn process_action(self: Box<Self>, action: NewMapMenuAction, world: &mut World) -> Box<GameState> {
+    fn menu_mut(&mut self) -> &mut ::menu::Menu<NewMapMenuAction> {
+        &mut self.menu
+    }
+
+    fn process_action(mut self: Box<Self>, action: NewMapMenuAction, world: &mut World) -> Box<GameState> {
         use self::NewMapMenuAction::*;
 
+        {
+            let level = &mut self.level_builder;
+            match action {
+                SetPreset(index) => {
+                    if let Some(preset) = ::level::Preset::iter_variants().nth(index) {
+                        *level = ::level::LevelBuilder { theme: level.theme, ..preset.level_builder() };
+                    }
+                }
+                SetSize(size) => level.half_size = (size as usize - 1) / 2,
+                SetPercent(percent) => level.percent = percent as f64,
+                SetXShift(shift) => level.x_shift = shift,
+                SetYShift(shift) => level.y_shift = shift,
+                SetZShift(shift) => level.z_shift = shift,
+                SetColumns(columns) => level.columns = columns as usize,
+                SetUnit(unit) => level.unit = unit,
+                SetMine(mine) => level.mine = mine as usize,
+                SetTarget(target) => level.target = target as usize,
+                SetRocketLauncher(rocket_launcher) => level.rocket_launcher = rocket_launcher as usize,
+                SetTheme(index) => {
+                    if let Some(theme) = ::texture::Theme::iter_variants().nth(index) {
+                        level.theme = theme;
+                    }
+                }
+                Play => {
+                    level.build(world);
+                    world.write_resource::<::resource::Save>().set_level_builder_lazy(level);
+                }
+            }
+        }
+
         match action {
-            Play => {
-                ::level::LevelBuilder {
-                    half_size: 9,
-                    x_shift: false,
-                    y_shift: false,
-                    z_shift: false,
-                    percent: 5.0,
-                    unit: 1.0,
-                    columns: 0,
-                    rocket_launcher: 1,
-                    mine: 1,
-                    target: 1,
-                }.build(world);
-                Box::new(Game::new()) as Box<GameState>
+            Play => Box::new(Game::new(world)) as Box<GameState>,
+            _ => {
+                let menu = NewMapMenu::create_menu(&self.level_builder);
+                self.menu.refresh(menu);
+                self
             }
         }
     }
 }
 
+struct SettingsMenu {
+    menu: ::menu::Menu<SettingsMenuAction>,
+    /// Configuration when the menu was opened, only the changes are saved
+    original: ::configuration::Configuration,
+    configuration: ::configuration::Configuration,
+    errors: Vec<String>,
+    stacked_state: Box<GameState>,
+}
+
+impl SettingsMenu {
+    pub fn new(world: &World, stacked_state: Box<GameState>) -> Self {
+        let configuration = world.read_resource::<::configuration::Configuration>().clone();
+        SettingsMenu {
+            menu: SettingsMenu::create_menu(&configuration),
+            original: configuration.clone(),
+            configuration,
+            errors: vec![],
+            stacked_state,
+        }
+    }
+
+    fn create_menu(cfg: &::configuration::Configuration) -> ::menu::Menu<SettingsMenuAction> {
+        use self::SettingsMenuAction::*;
+
+        ::menu::MenuBuilder::new()
+            .add_slider("FPS".to_string(), cfg.fps as f32, 10.0, 240.0, 5.0, SetFps)
+            .add_slider("Text scale".to_string(), cfg.text_scale, 10.0, 300.0, 10.0, SetTextScale)
+            .add_submenu("Colors".to_string(), ::menu::MenuBuilder::new()
+                .add_slider("Black".to_string(), cfg.color_black, 0.0, 1.0, 0.05, SetColorBlack)
+                .add_slider("White".to_string(), cfg.color_white, 0.0, 1.0, 0.05, SetColorWhite)
+            )
+            .add_submenu("Outline".to_string(), ::menu::MenuBuilder::new()
+                .add_toggle("Enabled".to_string(), cfg.outline, SetOutline)
+                .add_slider("Width".to_string(), cfg.outline_width, 0.5, 5.0, 0.5, SetOutlineWidth)
+                .add_slider("Falloff".to_string(), cfg.outline_falloff, 1.0, 30.0, 1.0, SetOutlineFalloff)
+            )
+            .add_submenu("Minimap".to_string(), ::menu::MenuBuilder::new()
+                .add_toggle("Enabled".to_string(), cfg.minimap, SetMinimap)
+                .add_toggle("Fog of war".to_string(), cfg.minimap_fog_of_war, SetMinimapFogOfWar)
+                .add_slider("Size".to_string(), cfg.minimap_size, 0.1, 1.0, 0.05, SetMinimapSize)
+            )
+            .add_submenu("Audio".to_string(), ::menu::MenuBuilder::new()
+                .add_slider("Effects volume".to_string(), cfg.effects_volume, 0.0, 1.0, 0.05, SetEffectsVolume)
+                .add_slider("Music volume".to_string(), cfg.music_volume, 0.0, 1.0, 0.05, SetMusicVolume)
+            )
+            .add_submenu("Flight".to_string(), ::menu::MenuBuilder::new()
+                .add_slider("Angular damping".to_string(), cfg.flight_control_ang_damping, 0.0, 1.0, 0.01, SetFlightAngDamping)
+                .add_slider("Linear damping".to_string(), cfg.flight_control_lin_damping, 0.0, 1.0, 0.01, SetFlightLinDamping)
+                .add_slider("Boost force".to_string(), cfg.flight_control_power_force, 0.0, 1.0, 0.01, SetFlightPowerForce)
+                .add_slider("Default force".to_string(), cfg.flight_control_default_power_force, 0.0, 1.0, 0.01, SetFlightDefaultPowerForce)
+                .add_slider("Direction force".to_string(), cfg.flight_control_direction_force, 0.0, 0.01, 0.0001, SetFlightDirectionForce)
+            )
+            .add_middle("Save".to_string(), Save)
+            .add_middle("Cancel".to_string(), Cancel)
+            .build()
+    }
+}
+
 #[derive(Clone, Copy)]
-enum NewMapMenuAction {
-    Play,
+pub enum SettingsMenuAction {
+    SetFps(f32),
+    SetTextScale(f32),
+    SetColorBlack(f32),
+    SetColorWhite(f32),
+    SetOutline(bool),
+    SetOutlineWidth(f32),
+    SetOutlineFalloff(f32),
+    SetMinimap(bool),
+    SetMinimapFogOfWar(bool),
+    SetMinimapSize(f32),
+    SetEffectsVolume(f32),
+    SetMusicVolume(f32),
+    SetFlightAngDamping(f32),
+    SetFlightLinDamping(f32),
+    SetFlightPowerForce(f32),
+    SetFlightDefaultPowerForce(f32),
+    SetFlightDirectionForce(f32),
+    Save,
+    Cancel,
 }
 
-impl GameState for NewMapMenu {
-    fn update_draw_ui(self: Box<Self>, world: &mut World) -> Box<GameState> {
-        let mut text = world.write_resource::<::resource::Text>();
-        let font = world.read_resource::<::resource::Font>();
-        text.global = self.menu.glyphs(&font);
+impl MenuState for SettingsMenu {
+    type Action = SettingsMenuAction;
 
-        self
+    fn menu(&self) -> &::menu::Menu<SettingsMenuAction> {
+        &self.menu
     }
 
-    fn winit_event(mut self: Box<Self>, event: ::winit::Event, world: &mut World) -> Box<GameState> {
-        let action = {
-            let controllers = world.read_resource::<::resource::PlayersControllers>();
-            self.menu.winit_event(event, None, &controllers)
-        };
-        if let Some(action) = action {
-            self.process_action(action, world)
-        } else {
-            self
-        }
+    fn menu_mut(&mut self) -> &mut ::menu::Menu<SettingsMenuAction> {
+        &mut self.menu
     }
 
-    fn gilrs_event(
-        mut self: Box<Self>,
-        _id: usize,
-        event: ::gilrs::EventType,
-        world: &mut World,
-    ) -> Box<GameState> {
-        if let Some(action) = self.menu.gilrs_event(event) {
-            self.process_action(action, world)
-        } else {
-            self
+    fn process_action(mut self: Box<Self>, action: SettingsMenuAction, _world: &mut World) -> Box<GameState> {
+        use self::SettingsMenuAction::*;
+
+        {
+            let cfg = &mut self.configuration;
+            match action {
+                SetFps(fps) => cfg.fps = fps as usize,
+                SetTextScale(scale) => cfg.text_scale = scale,
+                SetColorBlack(black) => cfg.color_black = black,
+                SetColorWhite(white) => cfg.color_white = white,
+                SetOutline(outline) => cfg.outline = outline,
+                SetOutlineWidth(width) => cfg.outline_width = width,
+                SetOutlineFalloff(falloff) => cfg.outline_falloff = falloff,
+                SetMinimap(minimap) => cfg.minimap = minimap,
+                SetMinimapFogOfWar(fog_of_war) => cfg.minimap_fog_of_war = fog_of_war,
+                SetMinimapSize(size) => cfg.minimap_size = size,
+                SetEffectsVolume(volume) => cfg.effects_volume = volume,
+                SetMusicVolume(volume) => cfg.music_volume = volume,
+                SetFlightAngDamping(damping) => cfg.flight_control_ang_damping = damping,
+                SetFlightLinDamping(damping) => cfg.flight_control_lin_damping = damping,
+                SetFlightPowerForce(force) => cfg.flight_control_power_force = force,
+                SetFlightDefaultPowerForce(force) => cfg.flight_control_default_power_force = force,
+                SetFlightDirectionForce(force) => cfg.flight_control_direction_force = force,
+                Save | Cancel => (),
+            }
         }
-    }
 
-    fn gilrs_gamepad_state(
-        self: Box<Self>,
-        _id: usize,
-        _gamepad: &::gilrs::Gamepad,
-        _world: &mut World,
-    ) -> Box<GameState> {
-        self
+        match action {
+            Save => {
+                let result = self.configuration.check()
+                    .and_then(|()| self.configuration.save_changes(&self.original).map_err(|e| vec![e]));
+                match result {
+                    Ok(()) => self.stacked_state,
+                    Err(errors) => {
+                        self.errors = errors;
+                        self
+                    }
+                }
+            }
+            Cancel => self.stacked_state,
+            _ => {
+                self.errors.clear();
+                self
+            }
+        }
     }
 
-    fn paused(&self, _world: &World) -> bool {
-        true
+    fn description(&self, _world: &World) -> Vec<String> {
+        let mut description = vec![];
+        if ::configuration::Configuration::local_override() {
+            description.push("Warning ↔ local configuration.ron overrides saved settings".to_string());
+        }
+        description.extend(self.errors.iter().map(|error| format!("Error ↔ {}", error)));
+        description
     }
 }
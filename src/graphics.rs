@@ -1,6 +1,6 @@
 use vulkano::device::{Device, DeviceExtensions, Queue};
 use vulkano::swapchain::{self, Surface, Swapchain, SwapchainCreationError};
-use vulkano::sampler::Sampler;
+use vulkano::sampler::{Filter, MipmapMode, Sampler, SamplerAddressMode};
 use vulkano::image::{Dimensions, ImageUsage, ImmutableImage};
 use vulkano::image::attachment::AttachmentImage;
 use vulkano::image::swapchain::SwapchainImage;
@@ -14,8 +14,8 @@ use vulkano::descriptor::descriptor_set::{DescriptorSet, FixedSizeDescriptorSets
                                           PersistentDescriptorSet};
 use vulkano::command_buffer::pool::standard::StandardCommandPoolAlloc;
 use vulkano::command_buffer::{AutoCommandBuffer, AutoCommandBufferBuilder, DynamicState};
-use vulkano::instance::{PhysicalDevice, PhysicalDeviceType};
-use vulkano::sync::{now, GpuFuture};
+use vulkano::instance::{Instance, PhysicalDevice, PhysicalDeviceType};
+use vulkano::sync::{now, AccessFlagBits, GpuFuture, PipelineStages};
 use vulkano::image::ImageLayout;
 use vulkano::format::{ClearValue, Format};
 use vulkano;
@@ -23,16 +23,56 @@ use alga::general::SubsetOf;
 use rand::{thread_rng, Rng};
 use rand::distributions::{Distribution, Range};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::path::PathBuf;
+use app_dirs2::{app_dir, AppDataType};
 use specs::{Join, World};
 use show_message::{OkOrShow, SomeOrShow};
 use std::f32::consts::PI;
 use std::collections::HashMap;
 
+/// Color of the trails of player killers, players use their slot color
+const KILLER_TRAIL_COLOR: [f32; 3] = [0.9, 0.35, 0.3];
+/// Color of each player slot
+const PLAYER_COLORS: [::colors::GenPale; 3] = [
+    ::colors::GenPale::Color0,
+    ::colors::GenPale::Color3,
+    ::colors::GenPale::Color6,
+];
+/// Color of shapes in physic debug mode, indexed by entity::Group
+const GROUP_COLORS: [[f32; 3]; 5] = [
+    [0.35, 0.75, 0.4],
+    [0.6, 0.6, 0.6],
+    [0.3, 0.5, 0.9],
+    [0.9, 0.35, 0.3],
+    [0.9, 0.6, 0.2],
+];
+const UNKNOWN_GROUP_COLOR: [f32; 3] = [1.0, 0.0, 1.0];
+/// Color of the thrust behind ships
+const THRUST_COLOR: [f32; 3] = [1.0, 0.6, 0.2];
+/// Size of a face of the skybox cubemap
+const SKYBOX_SIZE: u32 = 512;
+/// Color format of the render pass when there is no swapchain
+const OFFSCREEN_FORMAT: Format = Format::R8G8B8A8Unorm;
+/// Dimensions of glyph cache and screenshots when there is no swapchain
+const OFFSCREEN_CACHE_DIMENSIONS: [u32; 2] = [1280, 720];
+/// Formats of the attachments written by the world and read by the outline subpass
+const SCENE_FORMAT: Format = Format::R16G16B16A16Sfloat;
+const INK_FORMAT: Format = Format::R16G16Sfloat;
+/// Far plane of the perspective, also the distance written where nothing is drawn
+const FAR_PLANE: f32 = 100.0;
+// IDEA: all are good but
+// gaussian is too grey
+// nearest looks pixelized
+const TEXTURE_GENERATION_FILTER: ::image::FilterType = ::image::FilterType::Lanczos3;
+
 // FIXME: for performance: cache those buffers with a hash of the text corresponding
 pub struct TextBuffers {
     players: [Option<Arc<CpuAccessibleBuffer<[TextVertex]>>>; 3],
+    huds: [Option<Arc<CpuAccessibleBuffer<[TextVertex]>>>; 3],
     global: Option<Arc<CpuAccessibleBuffer<[TextVertex]>>>,
+    notification: Option<Arc<CpuAccessibleBuffer<[TextVertex]>>>,
+    debug: Option<Arc<CpuAccessibleBuffer<[TextVertex]>>>,
 }
 
 #[derive(Debug, Clone)]
@@ -42,6 +82,27 @@ struct TextVertex {
 }
 impl_vertex!(TextVertex, position, tex_position);
 
+#[derive(Debug, Clone)]
+struct ScreenVertex {
+    position: [f32; 2],
+}
+impl_vertex!(ScreenVertex, position);
+
+/// Vertex in world coordinates of the line pipeline
+#[derive(Debug, Clone)]
+pub struct LineVertex {
+    position: [f32; 3],
+}
+impl_vertex!(LineVertex, position);
+
+impl LineVertex {
+    pub fn new(position: &::na::Vector3<f32>) -> Self {
+        LineVertex {
+            position: [position[0], position[1], position[2]],
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Vertex {
     pub position: [f32; 3],
@@ -86,13 +147,97 @@ impl Vertex {
     }
 }
 
+/// Physical device as shown by the device selector and `--list-devices`
+#[derive(Clone)]
+pub struct DeviceInfo {
+    pub name: String,
+    pub ty: PhysicalDeviceType,
+    pub uuid: [u8; 16],
+    pub api_version: String,
+    /// Size of device local memory in MiB
+    pub memory: u64,
+    /// Has a graphics queue and supports swapchains
+    pub usable: bool,
+}
+
+impl DeviceInfo {
+    pub fn list(instance: &Arc<Instance>) -> Vec<DeviceInfo> {
+        PhysicalDevice::enumerate(instance)
+            .map(|physical| {
+                let version = physical.api_version();
+                DeviceInfo {
+                    name: physical.name().to_string(),
+                    ty: physical.ty(),
+                    uuid: physical.uuid().clone(),
+                    api_version: format!("{}.{}.{}", version.major, version.minor, version.patch),
+                    memory: physical.memory_heaps()
+                        .filter(|heap| heap.is_device_local())
+                        .map(|heap| heap.size() as u64)
+                        .sum::<u64>() / (1024 * 1024),
+                    usable: Graphics::is_usable(physical),
+                }
+            })
+            .collect()
+    }
+
+    pub fn description(&self) -> String {
+        format!(
+            "{} ({:?}, Vulkan {}, {} MiB{})",
+            self.name,
+            self.ty,
+            self.api_version,
+            self.memory,
+            if self.usable { "" } else { ", unusable" },
+        )
+    }
+}
+
+/// Devices available and the one in use
+pub struct Devices {
+    pub list: Vec<DeviceInfo>,
+    pub current: [u8; 16],
+    /// Saved device that couldn't be used at start
+    pub unavailable: Option<[u8; 16]>,
+}
+
+impl Devices {
+    pub fn new(list: Vec<DeviceInfo>, current: [u8; 16], saved: Option<[u8; 16]>) -> Self {
+        Devices {
+            list,
+            current,
+            unavailable: saved.filter(|&uuid| uuid != current),
+        }
+    }
+
+    /// Warning if the saved device couldn't be used
+    pub fn fallback_message(&self) -> Option<String> {
+        let name = |uuid: [u8; 16]| {
+            self.list.iter()
+                .find(|device| device.uuid == uuid)
+                .map_or("unknown device".to_string(), |device| device.name.clone())
+        };
+        self.unavailable.map(|uuid| {
+            format!("Saved Vulkan device {} not available, falling back to {}", name(uuid), name(self.current))
+        })
+    }
+}
+
 pub struct Graphics {
     pub queue: Arc<Queue>,
     pub device: Arc<Device>,
-    pub swapchain: Arc<Swapchain<::winit::Window>>,
+    /// None when rendering offscreen only
+    pub swapchain: Option<Arc<Swapchain<::winit::Window>>>,
+    pub present_mode: ::display::PresentMode,
     pub render_pass: Arc<RenderPass<CustomRenderPassDesc>>,
     pub pipeline: Arc<GraphicsPipelineAbstract + Sync + Send>,
     pub text_pipeline: Arc<GraphicsPipelineAbstract + Sync + Send>,
+    pub hud_pipeline: Arc<GraphicsPipelineAbstract + Sync + Send>,
+    pub line_pipeline: Arc<GraphicsPipelineAbstract + Sync + Send>,
+    pub skybox_pipeline: Arc<GraphicsPipelineAbstract + Sync + Send>,
+    pub skybox_descriptor_set: Arc<DescriptorSet + Send + Sync + 'static>,
+    pub outline_pipeline: Arc<GraphicsPipelineAbstract + Sync + Send>,
+    /// Scene and ink attachments of the framebuffers, none without swapchain
+    pub outline_descriptor_set: Option<Arc<DescriptorSet + Send + Sync + 'static>>,
     pub framebuffers: Vec<Arc<FramebufferAbstract + Sync + Send>>,
 
     pub camera_descriptor_sets_pool:
@@ -105,10 +250,15 @@ pub struct Graphics {
     pub cuboid_vertex_buffer: Arc<ImmutableBuffer<[Vertex]>>,
     pub cylinder_vertex_buffer: Arc<ImmutableBuffer<[Vertex]>>,
     pub ball_vertex_buffer: Arc<ImmutableBuffer<[Vertex]>>,
+    pub ship_vertex_buffer: Arc<ImmutableBuffer<[Vertex]>>,
+    screen_vertex_buffer: Arc<ImmutableBuffer<[ScreenVertex]>>,
 
     pub unlocal_texture_descriptor_set: Arc<DescriptorSet + Send + Sync + 'static>,
     pub player_position_memory: [Option<::na::Isometry3<f32>>; 3],
     pub need_update_glyph_cache: bool,
+    configuration_generation: usize,
+    palette: ::colors::Palette,
+    theme: ::texture::Theme,
 
     // TODO: maybe use an array
     pub tile_assets: HashMap<::tile::TileSize, (Arc<DescriptorSet + Send + Sync + 'static>, Arc<ImmutableBuffer<[Vertex]>>, [f32; 3])>,
@@ -158,15 +308,51 @@ impl Graphics {
         (cache, cache_pixel_buffer, cache_image_set)
     }
 
+    /// Depth, scene and ink attachments and the descriptor set reading scene and ink
+    fn intermediate_attachments(
+        device: &Arc<Device>,
+        outline_pipeline: &Arc<GraphicsPipelineAbstract + Sync + Send>,
+        dimensions: [u32; 2],
+    ) -> (
+        Arc<AttachmentImage>,
+        Arc<AttachmentImage>,
+        Arc<AttachmentImage>,
+        Arc<DescriptorSet + Send + Sync + 'static>,
+    ) {
+        let depth_buffer_attachment =
+            AttachmentImage::transient(device.clone(), dimensions, Format::D16Unorm).unwrap();
+        let scene_attachment =
+            AttachmentImage::transient_input_attachment(device.clone(), dimensions, SCENE_FORMAT)
+                .unwrap();
+        let ink_attachment =
+            AttachmentImage::transient_input_attachment(device.clone(), dimensions, INK_FORMAT)
+                .unwrap();
+
+        let outline_descriptor_set = PersistentDescriptorSet::start(outline_pipeline.clone(), 0)
+            .add_image(scene_attachment.clone())
+            .unwrap()
+            .add_image(ink_attachment.clone())
+            .unwrap()
+            .build()
+            .unwrap();
+
+        (
+            depth_buffer_attachment,
+            scene_attachment,
+            ink_attachment,
+            Arc::new(outline_descriptor_set) as Arc<_>,
+        )
+    }
+
     pub fn framebuffers_and_descriptors(
         device: &Arc<Device>,
         images: &Vec<Arc<SwapchainImage<::winit::Window>>>,
         render_pass: &Arc<RenderPass<CustomRenderPassDesc>>,
-    ) -> (Vec<Arc<FramebufferAbstract + Sync + Send>>, ()) {
+        outline_pipeline: &Arc<GraphicsPipelineAbstract + Sync + Send>,
+    ) -> (Vec<Arc<FramebufferAbstract + Sync + Send>>, Arc<DescriptorSet + Send + Sync + 'static>) {
         // FIXME: one depth buffer for each image ?
-        let depth_buffer_attachment =
-            AttachmentImage::transient(device.clone(), images[0].dimensions(), Format::D16Unorm)
-                .unwrap();
+        let (depth_buffer_attachment, scene_attachment, ink_attachment, outline_descriptor_set) =
+            Graphics::intermediate_attachments(device, outline_pipeline, images[0].dimensions());
 
         let framebuffers = images
             .iter()
@@ -177,22 +363,38 @@ impl Graphics {
                         .unwrap()
                         .add(depth_buffer_attachment.clone())
                         .unwrap()
+                        .add(scene_attachment.clone())
+                        .unwrap()
+                        .add(ink_attachment.clone())
+                        .unwrap()
                         .build()
                         .unwrap(),
                 ) as Arc<_>
             })
             .collect::<Vec<_>>();
 
-        (framebuffers, ())
+        (framebuffers, outline_descriptor_set)
+    }
+
+    fn is_usable(physical: PhysicalDevice) -> bool {
+        DeviceExtensions::supported_by_device(physical).khr_swapchain
+            && physical.queue_families().any(|q| q.supports_graphics() && q.supports_compute())
     }
 
-    pub fn new(window: &Arc<Surface<::winit::Window>>, save: &mut ::resource::Save) -> Graphics {
-        let physical = PhysicalDevice::enumerate(window.instance())
+    /// Prefer the saved device, fall back on the best type among the usable ones
+    fn choose_physical_device<'a, F>(
+        instance: &'a Arc<Instance>,
+        saved_uuid: Option<[u8; 16]>,
+        usable: F,
+    ) -> PhysicalDevice<'a>
+    where
+        F: Fn(PhysicalDevice) -> bool,
+    {
+        PhysicalDevice::enumerate(instance)
+            .filter(|&device| usable(device))
             .max_by_key(|device| {
-                if let Some(uuid) = save.vulkan_device_uuid().as_ref() {
-                    if uuid == device.uuid() {
-                        return 100;
-                    }
+                if saved_uuid.as_ref() == Some(device.uuid()) {
+                    return 100;
                 }
                 match device.ty() {
                     PhysicalDeviceType::IntegratedGpu => 4,
@@ -202,8 +404,22 @@ impl Graphics {
                     PhysicalDeviceType::Other => 0,
                 }
             })
-            .some_or_show("Failed to enumerate Vulkan devices");
-        save.set_vulkan_device_uuid_lazy(physical.uuid());
+            .some_or_show("Failed to find a usable Vulkan device")
+    }
+
+    pub fn new(
+        window: &Arc<Surface<::winit::Window>>,
+        save: &mut ::resource::Save,
+        configuration: &::configuration::Configuration,
+    ) -> Graphics {
+        let physical = Graphics::choose_physical_device(window.instance(), *save.vulkan_device_uuid(), |device| {
+            Graphics::is_usable(device)
+                && device.queue_families().any(|q| window.is_supported(q).unwrap_or(false))
+        });
+        // Keep the saved device so it is used again once available
+        if save.vulkan_device_uuid().is_none() {
+            save.set_vulkan_device_uuid_lazy(physical.uuid());
+        }
 
         let queue_family = physical
             .queue_families()
@@ -255,15 +471,60 @@ impl Graphics {
                 &queue,
                 swapchain::SurfaceTransform::Identity,
                 swapchain::CompositeAlpha::Opaque,
-                swapchain::PresentMode::Fifo,
+                save.display().present_mode.supported_or_fifo(&caps),
                 true,
                 None,
             ).expect("failed to create swapchain")
         };
 
+        let present_mode = save.display().present_mode;
+        Graphics::with_device(device, queue, Some((swapchain, images)), present_mode, configuration)
+    }
+
+    /// Graphics without window nor swapchain, only able to render offscreen
+    pub fn new_headless(
+        instance: &Arc<Instance>,
+        device_uuid: Option<[u8; 16]>,
+        configuration: &::configuration::Configuration,
+    ) -> Graphics {
+        let physical = Graphics::choose_physical_device(instance, device_uuid, |device| {
+            device.queue_families().any(|q| q.supports_graphics())
+        });
+
+        let queue_family = physical
+            .queue_families()
+            .find(|&q| q.supports_graphics())
+            .some_or_show("Failed to find a vulkan graphical queue family");
+
+        let (device, mut queues) = Device::new(
+            physical,
+            physical.supported_features(),
+            &DeviceExtensions::none(),
+            [(queue_family, 0.5)].iter().cloned(),
+        ).ok_or_show(|e| format!("Failed to create vulkan device: {}", e));
+
+        let queue = queues
+            .next()
+            .some_or_show("Failed to find queue with supported features");
+
+        Graphics::with_device(device, queue, None, ::display::PresentMode::Vsync, configuration)
+    }
+
+    fn with_device(
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        swapchain: Option<(Arc<Swapchain<::winit::Window>>, Vec<Arc<SwapchainImage<::winit::Window>>>)>,
+        present_mode: ::display::PresentMode,
+        configuration: &::configuration::Configuration,
+    ) -> Graphics {
+        let (format, dimensions) = match swapchain {
+            Some((ref swapchain, _)) => (swapchain.format(), swapchain.dimensions()),
+            None => (OFFSCREEN_FORMAT, OFFSCREEN_CACHE_DIMENSIONS),
+        };
+
         let render_pass = Arc::new(
             CustomRenderPassDesc {
-                swapchain_image_format: swapchain.format(),
+                swapchain_image_format: format,
             }.build_render_pass(device.clone())
                 .unwrap(),
         );
@@ -272,6 +533,14 @@ impl Graphics {
         let fs = fs::Shader::load(device.clone()).expect("failed to create shader module");
         let text_vs = text_vs::Shader::load(device.clone()).expect("failed to create shader module");
         let text_fs = text_fs::Shader::load(device.clone()).expect("failed to create shader module");
+        let skybox_vs = skybox_vs::Shader::load(device.clone()).expect("failed to create shader module");
+        let skybox_fs = skybox_fs::Shader::load(device.clone()).expect("failed to create shader module");
+        let outline_vs = outline_vs::Shader::load(device.clone()).expect("failed to create shader module");
+        let hud_vs = hud_vs::Shader::load(device.clone()).expect("failed to create shader module");
+        let hud_fs = hud_fs::Shader::load(device.clone()).expect("failed to create shader module");
+        let line_vs = line_vs::Shader::load(device.clone()).expect("failed to create shader module");
+        let line_fs = line_fs::Shader::load(device.clone()).expect("failed to create shader module");
+        let outline_fs = outline_fs::Shader::load(device.clone()).expect("failed to create shader module");
 
         let pipeline = Arc::new(
             vulkano::pipeline::GraphicsPipeline::start()
@@ -296,11 +565,92 @@ impl Graphics {
                 .viewports_dynamic_scissors_irrelevant(1)
                 .fragment_shader(text_fs.main_entry_point(), ())
                 .blend_alpha_blending()
+                .render_pass(vulkano::framebuffer::Subpass::from(render_pass.clone(), 1).unwrap())
+                .build(device.clone())
+                .unwrap(),
+        ) as Arc<GraphicsPipelineAbstract + Send + Sync>;
+
+        let hud_pipeline = Arc::new(
+            vulkano::pipeline::GraphicsPipeline::start()
+                .vertex_input_single_buffer::<::hud::HudVertex>()
+                .vertex_shader(hud_vs.main_entry_point(), ())
+                .triangle_list()
+                .viewports_dynamic_scissors_irrelevant(1)
+                .fragment_shader(hud_fs.main_entry_point(), ())
+                .blend_alpha_blending()
+                .render_pass(vulkano::framebuffer::Subpass::from(render_pass.clone(), 1).unwrap())
+                .build(device.clone())
+                .unwrap(),
+        ) as Arc<GraphicsPipelineAbstract + Send + Sync>;
+
+        let line_pipeline = Arc::new(
+            vulkano::pipeline::GraphicsPipeline::start()
+                .vertex_input_single_buffer::<LineVertex>()
+                .vertex_shader(line_vs.main_entry_point(), ())
+                .line_list()
+                .viewports_dynamic_scissors_irrelevant(1)
+                .fragment_shader(line_fs.main_entry_point(), ())
+                .blend_alpha_blending()
+                .render_pass(vulkano::framebuffer::Subpass::from(render_pass.clone(), 1).unwrap())
+                .build(device.clone())
+                .unwrap(),
+        ) as Arc<GraphicsPipelineAbstract + Send + Sync>;
+
+        // Drawn first without depth so everything else is in front of it
+        let skybox_pipeline = Arc::new(
+            vulkano::pipeline::GraphicsPipeline::start()
+                .vertex_input_single_buffer::<Vertex>()
+                .vertex_shader(skybox_vs.main_entry_point(), ())
+                .triangle_list()
+                .viewports_dynamic_scissors_irrelevant(1)
+                .fragment_shader(skybox_fs.main_entry_point(), ())
                 .render_pass(vulkano::framebuffer::Subpass::from(render_pass.clone(), 0).unwrap())
                 .build(device.clone())
                 .unwrap(),
         ) as Arc<GraphicsPipelineAbstract + Send + Sync>;
 
+        // Composite the scene with the ink of the outlines
+        let outline_pipeline = Arc::new(
+            vulkano::pipeline::GraphicsPipeline::start()
+                .vertex_input_single_buffer::<ScreenVertex>()
+                .vertex_shader(outline_vs.main_entry_point(), ())
+                .triangle_list()
+                .viewports_dynamic_scissors_irrelevant(1)
+                .fragment_shader(outline_fs.main_entry_point(), ())
+                .render_pass(vulkano::framebuffer::Subpass::from(render_pass.clone(), 1).unwrap())
+                .build(device.clone())
+                .unwrap(),
+        ) as Arc<GraphicsPipelineAbstract + Send + Sync>;
+
+        let (skybox_texture, _skybox_future) = ImmutableImage::from_iter(
+            ::skybox::generate(SKYBOX_SIZE).into_iter(),
+            Dimensions::Cubemap { size: SKYBOX_SIZE },
+            Format::R8Unorm,
+            queue.clone(),
+        ).unwrap();
+
+        let skybox_sampler = Sampler::new(
+            device.clone(),
+            Filter::Linear,
+            Filter::Linear,
+            MipmapMode::Nearest,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            0.0,
+            1.0,
+            0.0,
+            0.0,
+        ).unwrap();
+
+        let skybox_descriptor_set = PersistentDescriptorSet::start(skybox_pipeline.clone(), 0)
+            .add_sampled_image(skybox_texture, skybox_sampler)
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let skybox_descriptor_set = Arc::new(skybox_descriptor_set) as Arc<_>;
+
         let camera_descriptor_sets_pool = FixedSizeDescriptorSetsPool::new(pipeline.clone(), 0);
         let view_buffer_pool =
             CpuBufferPool::<vs::ty::View>::new(device.clone(), BufferUsage::uniform_buffer());
@@ -433,25 +783,48 @@ impl Graphics {
             queue.clone(),
         ).unwrap();
 
-        let (framebuffers, ()) =
-            Graphics::framebuffers_and_descriptors(&device, &images, &render_pass);
+        let (ship_vertex_buffer, _future) = ImmutableBuffer::from_iter(
+            Vertex::from_obj(include_str!("ship.obj").to_string()).into_iter(),
+            BufferUsage::vertex_buffer(),
+            queue.clone(),
+        ).unwrap();
+
+        // A single triangle covering the screen
+        let (screen_vertex_buffer, _future) = ImmutableBuffer::from_iter(
+            [
+                ScreenVertex { position: [-1.0, -1.0] },
+                ScreenVertex { position: [3.0, -1.0] },
+                ScreenVertex { position: [-1.0, 3.0] },
+            ].iter()
+                .cloned(),
+            BufferUsage::vertex_buffer(),
+            queue.clone(),
+        ).unwrap();
 
-        // IDEA: all are good but
-        // gaussian is too grey
-        // nearest looks pixelized
-        let texture_generation_filter = ::image::FilterType::Lanczos3;
+        let (framebuffers, outline_descriptor_set) = match swapchain {
+            Some((_, ref images)) => {
+                let (framebuffers, outline_descriptor_set) = Graphics::framebuffers_and_descriptors(
+                    &device,
+                    images,
+                    &render_pass,
+                    &outline_pipeline,
+                );
+                (framebuffers, Some(outline_descriptor_set))
+            }
+            None => (vec![], None),
+        };
 
         let (unlocal_texture, _future) = {
             let dimensions = Dimensions::Dim2d {
-                width: ::CFG.unlocal_texture_size,
-                height: ::CFG.unlocal_texture_size,
+                width: configuration.unlocal_texture_size,
+                height: configuration.unlocal_texture_size,
             };
 
             let image = ::texture::generate_texture(
                 dimensions.width(),
                 dimensions.height(),
-                ::CFG.unlocal_texture_layers,
-                texture_generation_filter,
+                configuration.unlocal_texture_layers,
+                TEXTURE_GENERATION_FILTER,
                 false,
             );
 
@@ -466,45 +839,16 @@ impl Graphics {
         let mut tile_assets = HashMap::new();
         let mut _futures = (vec![], vec![]);
         for tile_size in ::tile::TileSize::iter_variants() {
-            let dimensions = Dimensions::Dim2d {
-                width: ::CFG.unlocal_texture_size * tile_size.width() as u32,
-                height: ::CFG.unlocal_texture_size * tile_size.height() as u32,
-            };
-
-            let image = ::texture::generate_texture(
-                dimensions.width(),
-                dimensions.height(),
-                ::CFG.unlocal_texture_layers,
-                texture_generation_filter,
-                false,
+            let (texture_descriptor_set, vertex_buffer, future) = Graphics::tile_asset(
+                &device,
+                &queue,
+                &pipeline,
+                configuration,
+                tile_size,
+                ::texture::Theme::default(),
             );
-
-            let (texture, future) = ImmutableImage::from_iter(
-                image.into_raw().iter().cloned(),
-                dimensions,
-                Format::R8Unorm,
-                queue.clone(),
-            ).unwrap();
             _futures.0.push(future);
 
-            let texture_descriptor_set = PersistentDescriptorSet::start(pipeline.clone(), 2)
-                .add_sampled_image(texture, Sampler::simple_repeat_linear(device.clone()))
-                .unwrap()
-                .build()
-                .unwrap();
-
-            let texture_descriptor_set = Arc::new(texture_descriptor_set) as Arc<_>;
-
-            let (vertex_buffer, future) = ImmutableBuffer::from_iter(
-                ::obj::generate_tile(tile_size.width(), tile_size.height())
-                    .iter()
-                    .cloned(),
-                BufferUsage::vertex_buffer(),
-                queue.clone(),
-            ).unwrap();
-
-            _futures.1.push(future);
-
             tile_assets.insert(
                 tile_size.clone(),
                 (texture_descriptor_set, vertex_buffer, [0.0; 3]),
@@ -515,15 +859,15 @@ impl Graphics {
         for shape in ::tube::Shape::iter_variants() {
             // TODO: load from file
             let dimensions = Dimensions::Dim2d {
-                width: ::CFG.unlocal_texture_size as u32,
-                height: ::CFG.unlocal_texture_size as u32,
+                width: configuration.unlocal_texture_size as u32,
+                height: configuration.unlocal_texture_size as u32,
             };
 
             let image = ::texture::generate_texture(
                 dimensions.width(),
                 dimensions.height(),
-                ::CFG.unlocal_texture_layers,
-                texture_generation_filter,
+                configuration.unlocal_texture_layers,
+                TEXTURE_GENERATION_FILTER,
                 false,
             );
 
@@ -533,7 +877,7 @@ impl Graphics {
                 Format::R8Unorm,
                 queue.clone(),
             ).unwrap();
-            _futures.0.push(future);
+            _futures.0.push(Box::new(future) as Box<GpuFuture>);
 
             let texture_descriptor_set = PersistentDescriptorSet::start(pipeline.clone(), 2)
                 .add_sampled_image(texture, Sampler::simple_repeat_linear(device.clone()))
@@ -571,7 +915,7 @@ impl Graphics {
             &device,
             &queue,
             &text_pipeline,
-            swapchain.dimensions(),
+            dimensions,
         );
 
         let mut graphics = Graphics {
@@ -580,15 +924,25 @@ impl Graphics {
             cache_image_set,
 
             need_update_glyph_cache: false,
+            configuration_generation: 0,
+            palette: ::colors::Palette::new(configuration),
+            theme: ::texture::Theme::default(),
             player_position_memory: [None; 3],
             future,
             device,
             queue,
-            swapchain,
+            swapchain: swapchain.map(|(swapchain, _)| swapchain),
+            present_mode,
             render_pass,
             framebuffers,
             pipeline,
             text_pipeline,
+            hud_pipeline,
+            line_pipeline,
+            skybox_pipeline,
+            skybox_descriptor_set,
+            outline_pipeline,
+            outline_descriptor_set,
 
             camera_descriptor_sets_pool,
             view_buffer_pool,
@@ -598,35 +952,145 @@ impl Graphics {
             cuboid_vertex_buffer,
             cylinder_vertex_buffer,
             ball_vertex_buffer,
+            ship_vertex_buffer,
+            screen_vertex_buffer,
 
             unlocal_texture_descriptor_set,
             tile_assets,
             tube_assets,
         };
 
-        graphics.reset_colors();
+        graphics.reset_colors(configuration);
 
         graphics
     }
 
-    pub fn reset_colors(&mut self) {
+    /// Texture and vertices of a tile, they share the layout of the unwrapped tile
+    fn tile_asset(
+        device: &Arc<Device>,
+        queue: &Arc<Queue>,
+        pipeline: &Arc<GraphicsPipelineAbstract + Sync + Send>,
+        configuration: &::configuration::Configuration,
+        tile_size: ::tile::TileSize,
+        theme: ::texture::Theme,
+    ) -> (Arc<DescriptorSet + Send + Sync + 'static>, Arc<ImmutableBuffer<[Vertex]>>, Box<GpuFuture>) {
+        let net = ::obj::TileNet::new(
+            tile_size.width(),
+            tile_size.height(),
+            configuration.unlocal_texture_size,
+        );
+        let dimensions = Dimensions::Dim2d {
+            width: net.dimensions[0],
+            height: net.dimensions[1],
+        };
+
+        let image = ::texture::generate_tile_texture(theme, &net, configuration.unlocal_texture_layers);
+
+        let (texture, texture_future) = ImmutableImage::from_iter(
+            image.into_raw().iter().cloned(),
+            dimensions,
+            Format::R8Unorm,
+            queue.clone(),
+        ).unwrap();
+
+        let texture_descriptor_set = PersistentDescriptorSet::start(pipeline.clone(), 2)
+            .add_sampled_image(texture, Sampler::simple_repeat_linear(device.clone()))
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let (vertex_buffer, vertex_future) = ImmutableBuffer::from_iter(
+            ::obj::generate_tile(&net).into_iter(),
+            BufferUsage::vertex_buffer(),
+            queue.clone(),
+        ).unwrap();
+
+        (
+            Arc::new(texture_descriptor_set) as Arc<_>,
+            vertex_buffer,
+            Box::new(texture_future.join(vertex_future)) as Box<_>,
+        )
+    }
+
+    /// Regenerate the tiles for the theme of the level
+    fn set_theme(&mut self, theme: ::texture::Theme, configuration: &::configuration::Configuration) {
+        self.theme = theme;
+        let mut future = self.future.take().unwrap();
+        for (tile_size, tile) in &mut self.tile_assets {
+            let (texture_descriptor_set, vertex_buffer, tile_future) = Graphics::tile_asset(
+                &self.device,
+                &self.queue,
+                &self.pipeline,
+                configuration,
+                *tile_size,
+                theme,
+            );
+            tile.0 = texture_descriptor_set;
+            tile.1 = vertex_buffer;
+            future = Box::new(future.join(tile_future)) as Box<_>;
+        }
+        self.future = Some(future);
+    }
+
+    /// Follow changes of the configuration and of the level
+    fn update_assets(&mut self, world: &World) {
+        let configuration_generation = world.read_resource::<::configuration::ConfigurationWatcher>().generation;
+        if configuration_generation != self.configuration_generation {
+            self.configuration_generation = configuration_generation;
+            self.reset_colors(&world.read_resource());
+        }
+
+        let theme = *world.read_resource::<::texture::Theme>();
+        if theme != self.theme {
+            self.set_theme(theme, &world.read_resource());
+        }
+    }
+
+    pub fn reset_colors(&mut self, configuration: &::configuration::Configuration) {
+        self.palette = ::colors::Palette::new(configuration);
         let mut colors = ::colors::GenPale::colors();
         thread_rng().shuffle(&mut colors);
         for tile in self.tile_assets.values_mut() {
-            tile.2 = colors.pop().unwrap().into();
+            tile.2 = self.palette.rgb(colors.pop().unwrap());
         }
     }
 
+    fn swapchain(&self) -> &Arc<Swapchain<::winit::Window>> {
+        self.swapchain.as_ref().expect("swapchain is only available with a window")
+    }
+
+    pub fn set_present_mode(&mut self, present_mode: ::display::PresentMode, window: &Arc<Surface<::winit::Window>>) {
+        self.present_mode = present_mode;
+        self.recreate(window);
+    }
+
     fn recreate(&mut self, window: &Arc<Surface<::winit::Window>>) {
         let mut remaining_try = 20;
         let recreate = loop {
-            let dimensions = window
+            let caps = window
                 .capabilities(self.device.physical_device())
-                .expect("failed to get surface capabilities")
-                .current_extent
-                .unwrap_or([1024, 768]);
+                .expect("failed to get surface capabilities");
+            let dimensions = caps.current_extent.unwrap_or([1024, 768]);
+            let image_usage = ImageUsage {
+                color_attachment: true,
+                ..ImageUsage::none()
+            };
 
-            let res = self.swapchain.recreate_with_dimension(dimensions);
+            let res = Swapchain::new(
+                self.device.clone(),
+                window.clone(),
+                self.swapchain().num_images(),
+                self.swapchain().format(),
+                dimensions,
+                1,
+                image_usage,
+                &self.queue,
+                swapchain::SurfaceTransform::Identity,
+                swapchain::CompositeAlpha::Opaque,
+                self.present_mode.supported_or_fifo(&caps),
+                true,
+                Some(self.swapchain()),
+            );
 
             if remaining_try == 0 {
                 break res;
@@ -643,17 +1107,22 @@ impl Graphics {
         };
 
         let (swapchain, images) = recreate.unwrap();
-        self.swapchain = swapchain;
+        self.swapchain = Some(swapchain);
 
-        let (framebuffers, ()) =
-            Graphics::framebuffers_and_descriptors(&self.device, &images, &self.render_pass);
+        let (framebuffers, outline_descriptor_set) = Graphics::framebuffers_and_descriptors(
+            &self.device,
+            &images,
+            &self.render_pass,
+            &self.outline_pipeline,
+        );
         self.framebuffers = framebuffers;
+        self.outline_descriptor_set = Some(outline_descriptor_set);
 
         let (cache, cache_pixel_buffer, cache_image_set) = Graphics::caches(
             &self.device,
             &self.queue,
             &self.text_pipeline,
-            self.swapchain.dimensions(),
+            self.swapchain().dimensions(),
         );
         self.cache = cache;
         self.cache_pixel_buffer = cache_pixel_buffer;
@@ -667,17 +1136,18 @@ impl Graphics {
         game_state: Box<::game_state::GameState>,
     ) -> Box<::game_state::GameState> {
         self.future.as_mut().unwrap().cleanup_finished();
+        self.update_assets(world);
 
         // On X with Xmonad and intel HD graphics the acquire stay sometimes forever
         let timeout = Duration::from_secs(2);
-        let mut next_image = swapchain::acquire_next_image(self.swapchain.clone(), Some(timeout));
+        let mut next_image = swapchain::acquire_next_image(self.swapchain().clone(), Some(timeout));
         loop {
             match next_image {
                 Err(vulkano::swapchain::AcquireError::OutOfDate)
                 | Err(vulkano::swapchain::AcquireError::Timeout) => {
                     self.recreate(&window);
                     next_image =
-                        swapchain::acquire_next_image(self.swapchain.clone(), Some(timeout));
+                        swapchain::acquire_next_image(self.swapchain().clone(), Some(timeout));
                 }
                 _ => break,
             }
@@ -694,7 +1164,7 @@ impl Graphics {
             .join(build_command_future)
             .then_execute(self.queue.clone(), command_buffer)
             .unwrap()
-            .then_swapchain_present(self.queue.clone(), self.swapchain.clone(), image_num)
+            .then_swapchain_present(self.queue.clone(), self.swapchain().clone(), image_num)
             .then_signal_fence_and_flush();
 
         match future {
@@ -714,8 +1184,11 @@ impl Graphics {
 
     fn build_text_buffers(&mut self, text: &mut ::resource::Text, mode: ::resource::Mode, dimensions: [u32; 2]) -> TextBuffers {
         text.players.iter()
+            .chain(text.huds.iter())
             .flat_map(|v| v)
             .chain(text.global.iter())
+            .chain(text.notification.iter())
+            .chain(text.debug.iter())
             .for_each(|glyph| self.cache.queue_glyph(0, glyph.clone()));
 
         let (cache_width, _) = self.cache.dimensions();
@@ -746,17 +1219,17 @@ impl Graphics {
             ).unwrap();
         }
 
-        let build_buffer = |glyphs: &Vec<_>, dimensions: [u32; 2]| {
+        let build_buffer = |glyphs: &Vec<_>, dimensions: [u32; 2], origin: [f32; 2]| {
             let vertices = glyphs.iter().flat_map(|g| {
                 if let Ok(Some((uv_rect, screen_rect))) = self.cache.rect_for(0, g) {
                     let gl_rect = ::rusttype::Rect {
                         min: ::rusttype::point(
-                            screen_rect.min.x as f32 / dimensions[0] as f32,
-                            screen_rect.min.y as f32 / dimensions[1] as f32,
+                            origin[0] + screen_rect.min.x as f32 / dimensions[0] as f32,
+                            origin[1] + screen_rect.min.y as f32 / dimensions[1] as f32,
                         ),
                         max: ::rusttype::point(
-                            screen_rect.max.x as f32 / dimensions[0] as f32,
-                            screen_rect.max.y as f32 / dimensions[1] as f32,
+                            origin[0] + screen_rect.max.x as f32 / dimensions[0] as f32,
+                            origin[1] + screen_rect.max.y as f32 / dimensions[1] as f32,
                         ),
                     };
                     vec!(
@@ -795,64 +1268,686 @@ impl Graphics {
         };
 
         let global = if text.global.len() != 0 {
-            Some(build_buffer(&text.global, dimensions))
+            Some(build_buffer(&text.global, dimensions, [0.0, 0.0]))
+        } else {
+            None
+        };
+
+        let notification = if text.notification.len() != 0 {
+            Some(build_buffer(&text.notification, dimensions, [-0.95, -0.95]))
+        } else {
+            None
+        };
+
+        let debug = if text.debug.len() != 0 {
+            Some(build_buffer(&text.debug, dimensions, ::debug::TEXT_ORIGIN))
         } else {
             None
         };
 
         let mut players = [None, None, None];
-        for (player, text_player) in text.players.iter().enumerate() {
-            if text_player.len() != 0 {
-                let viewport = mode.viewport_for_player(player, dimensions);
-                let player_dimensions = [
-                    viewport.dimensions[0] as u32,
-                    viewport.dimensions[1] as u32,
-                ];
-                players[player] = Some(build_buffer(&text_player, player_dimensions));
+        let mut huds = [None, None, None];
+        for player in 0..mode.number_of_player() {
+            let viewport = mode.viewport_for_player(player, dimensions);
+            let player_dimensions = [
+                viewport.dimensions[0] as u32,
+                viewport.dimensions[1] as u32,
+            ];
+            if text.players[player].len() != 0 {
+                players[player] = Some(build_buffer(&text.players[player], player_dimensions, [0.0, 0.0]));
+            }
+            if text.huds[player].len() != 0 {
+                huds[player] = Some(build_buffer(&text.huds[player], player_dimensions, [-0.95, -0.95]));
             }
         }
 
         // Clean text
         text.global.clear();
-        for text in &mut text.players {
+        text.notification.clear();
+        text.debug.clear();
+        for text in text.players.iter_mut().chain(text.huds.iter_mut()) {
             text.clear();
         }
 
         TextBuffers {
             players,
+            huds,
             global,
+            notification,
+            debug,
         }
     }
 
-    fn build_command_buffer(
-        &mut self,
-        image_num: usize,
-        world: &mut World,
-        game_state: Box<::game_state::GameState>,
-    ) -> (
-        AutoCommandBuffer<StandardCommandPoolAlloc>,
-        Box<::game_state::GameState>,
-        Box<::vulkano::sync::GpuFuture>,
-    ) {
-        let dimensions = self.swapchain.dimensions();
+    /// Boost bar and indicators of the hud of the player
+    fn hud_buffer(
+        &self,
+        world: &World,
+        player: usize,
+        player_pos: &::na::Isometry3<f32>,
+        viewport: &Viewport,
+    ) -> Arc<CpuAccessibleBuffer<[::hud::HudVertex]>> {
+        let view_perspective = camera_perspective(viewport).unwrap() * camera_view(player_pos).unwrap();
+        let aspect = viewport.dimensions[0] / viewport.dimensions[1];
+        let vertices = ::hud::shapes(world, player, player_pos, &view_perspective, aspect);
+        CpuAccessibleBuffer::from_iter(self.device.clone(), BufferUsage::vertex_buffer(), vertices.into_iter()).unwrap()
+    }
 
-        let screen_dynamic_state = DynamicState {
+    /// Maze and markers seen from behind the player in the top right corner of its viewport
+    fn draw_minimap(
+        &self,
+        mut command_buffer_builder: AutoCommandBufferBuilder,
+        world: &World,
+        player: usize,
+        player_pos: &::na::Isometry3<f32>,
+        viewport: &Viewport,
+    ) -> AutoCommandBufferBuilder {
+        let (fog_of_war, size, player_colors) = {
+            let configuration = world.read_resource::<::configuration::Configuration>();
+            if !configuration.minimap {
+                return command_buffer_builder;
+            }
+            let mut player_colors = [[0.0; 4]; 3];
+            for (color, slot_color) in player_colors.iter_mut().zip(PLAYER_COLORS.iter()) {
+                let rgb = self.palette.rgb(*slot_color);
+                *color = [rgb[0], rgb[1], rgb[2], 1.0];
+            }
+            (
+                configuration.minimap_fog_of_war,
+                configuration.minimap_size * viewport.dimensions[1],
+                player_colors,
+            )
+        };
+
+        let dynamic_state = DynamicState {
             viewports: Some(vec![
                 Viewport {
-                    origin: [0.0, 0.0],
-                    dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+                    origin: [
+                        viewport.origin[0] + viewport.dimensions[0] - size - ::minimap::MARGIN,
+                        viewport.origin[1] + ::minimap::MARGIN,
+                    ],
+                    dimensions: [size, size],
                     depth_range: 0.0..1.0,
                 },
             ]),
             ..DynamicState::none()
         };
+        let view_perspective = world.read_resource::<::minimap::MazeMap>()
+            .view_perspective(&player_pos.rotation);
 
-        let next_game_state = game_state.update_draw_ui(world);
-        let mut text_buffers = self.build_text_buffers(&mut world.write_resource(), *world.read_resource(), dimensions);
+        let walls = ::minimap::walls(world, player, fog_of_war);
+        let markers = ::minimap::markers(world, player, fog_of_war, &player_colors);
+        let lines = Some(walls).into_iter().chain(markers).collect();
+        self.draw_lines(command_buffer_builder, &dynamic_state, &view_perspective, lines)
+    }
 
-        let mut command_buffer_builder = AutoCommandBufferBuilder::primary_one_time_submit(
+    /// Draw line lists with their colors
+    fn draw_lines(
+        &self,
+        mut command_buffer_builder: AutoCommandBufferBuilder,
+        dynamic_state: &DynamicState,
+        view_perspective: &::na::Matrix4<f32>,
+        lines: Vec<(Vec<LineVertex>, [f32; 4])>,
+    ) -> AutoCommandBufferBuilder {
+        for (vertices, color) in lines.into_iter().filter(|&(ref vertices, _)| !vertices.is_empty()) {
+            let buffer = CpuAccessibleBuffer::from_iter(
+                self.device.clone(),
+                BufferUsage::vertex_buffer(),
+                vertices.into_iter(),
+            ).unwrap();
+            command_buffer_builder = command_buffer_builder.draw(
+                self.line_pipeline.clone(),
+                dynamic_state.clone(),
+                vec![buffer],
+                (),
+                line_vs::ty::Camera {
+                    view_perspective: (*view_perspective).into(),
+                    color,
+                },
+            ).unwrap();
+        }
+        command_buffer_builder
+    }
+
+    /// Current position of the player or the last known one
+    fn player_position(&self, world: &World, player: usize) -> Option<::na::Isometry3<f32>> {
+        let physic_world = world.read_resource::<::resource::PhysicWorld>();
+        let physic_bodies = world.read_storage::<::component::PhysicBody>();
+        let interpolation = world.read_resource::<::resource::Interpolation>();
+
+        world.read_resource::<::resource::PlayersEntities>()[player]
+            .and_then(|entity| physic_bodies.get(entity))
+            .map(|body| interpolation.position(body, &physic_world))
+            .or(self.player_position_memory[player])
+    }
+
+    /// Render the world seen by every player into an image, texts are not drawn
+    pub fn render_offscreen(&mut self, world: &World, dimensions: [u32; 2]) -> ::image::RgbaImage {
+        self.update_assets(world);
+        let format = self.render_pass.desc().swapchain_image_format;
+        let image = AttachmentImage::with_usage(
             self.device.clone(),
-            self.queue.family(),
+            dimensions,
+            format,
+            ImageUsage {
+                color_attachment: true,
+                transfer_source: true,
+                ..ImageUsage::none()
+            },
+        ).unwrap();
+        let (depth_buffer_attachment, scene_attachment, ink_attachment, outline_descriptor_set) =
+            Graphics::intermediate_attachments(&self.device, &self.outline_pipeline, dimensions);
+        let framebuffer = Arc::new(
+            Framebuffer::start(self.render_pass.clone())
+                .add(image.clone())
+                .unwrap()
+                .add(depth_buffer_attachment)
+                .unwrap()
+                .add(scene_attachment)
+                .unwrap()
+                .add(ink_attachment)
+                .unwrap()
+                .build()
+                .unwrap(),
+        );
+
+        let buffer = CpuAccessibleBuffer::from_iter(
+            self.device.clone(),
+            BufferUsage::transfer_destination(),
+            (0..dimensions[0] * dimensions[1] * 4).map(|_| 0u8),
+        ).unwrap();
+
+        let mut command_buffer_builder = AutoCommandBufferBuilder::primary_one_time_submit(
+            self.device.clone(),
+            self.queue.family(),
+        ).unwrap()
+            .begin_render_pass(framebuffer, false, CustomRenderPassDesc::clear_values())
+            .unwrap();
+
+        let mode = *world.read_resource::<::resource::Mode>();
+        for player in 0..mode.number_of_player() {
+            let viewport = mode.viewport_for_player(player, dimensions);
+            let dynamic_state = DynamicState {
+                viewports: Some(vec![viewport.clone()]),
+                ..DynamicState::none()
+            };
+
+            if let Some(player_pos) = self.player_position(world, player) {
+                command_buffer_builder = self.draw_world(command_buffer_builder, world, &dynamic_state, &viewport, &player_pos);
+            }
+        }
+
+        let screen_dynamic_state = DynamicState {
+            viewports: Some(vec![
+                Viewport {
+                    origin: [0.0, 0.0],
+                    dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+                    depth_range: 0.0..1.0,
+                },
+            ]),
+            ..DynamicState::none()
+        };
+        command_buffer_builder = self.draw_outline(
+            command_buffer_builder,
+            world,
+            outline_descriptor_set,
+            &screen_dynamic_state,
+        );
+
+        let command = command_buffer_builder
+            .end_render_pass()
+            .unwrap()
+            .copy_image_to_buffer(image, buffer.clone())
+            .unwrap()
+            .build()
+            .unwrap();
+
+        self.future
+            .take()
+            .unwrap()
+            .then_execute(self.queue.clone(), command)
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap()
+            .wait(None)
+            .unwrap();
+        self.future = Some(Box::new(now(self.device.clone())) as Box<_>);
+
+        let mut pixels = buffer.read().unwrap().to_vec();
+        // Swapchain images are often stored as BGRA
+        if format == Format::B8G8R8A8Unorm || format == Format::B8G8R8A8Srgb {
+            for pixel in pixels.chunks_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+        ::image::RgbaImage::from_raw(dimensions[0], dimensions[1], pixels).unwrap()
+    }
+
+    /// Render offscreen with the dimensions of the window and write a PNG in the screenshots directory
+    pub fn screenshot(&mut self, world: &World) -> Result<PathBuf, String> {
+        let dimensions = self.swapchain.as_ref()
+            .map_or(OFFSCREEN_CACHE_DIMENSIONS, |swapchain| swapchain.dimensions());
+        let image = self.render_offscreen(world, dimensions);
+
+        let mut path = app_dir(AppDataType::UserData, &::resource::APP_INFO, "screenshots")
+            .map_err(|e| format!("Failed to create screenshots directory: {}", e))?;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs() * 1000 + duration.subsec_millis() as u64)
+            .unwrap_or(0);
+        path.push(format!("screenshot_{}.png", timestamp));
+        // Never overwrite a previous screenshot
+        let mut count = 1;
+        while path.exists() {
+            path.set_file_name(format!("screenshot_{}_{}.png", timestamp, count));
+            count += 1;
+        }
+
+        image.save(&path)
+            .map_err(|e| format!("Failed to write screenshot {}: {}", path.display(), e))?;
+        Ok(path)
+    }
+
+    /// Go to the outline subpass and ink the scene drawn by draw_world
+    fn draw_outline(
+        &mut self,
+        command_buffer_builder: AutoCommandBufferBuilder,
+        world: &World,
+        outline_descriptor_set: Arc<DescriptorSet + Send + Sync + 'static>,
+        dynamic_state: &DynamicState,
+    ) -> AutoCommandBufferBuilder {
+        let configuration = world.read_resource::<::configuration::Configuration>();
+        command_buffer_builder
+            .next_subpass(false)
+            .unwrap()
+            .draw(
+                self.outline_pipeline.clone(),
+                dynamic_state.clone(),
+                vec![self.screen_vertex_buffer.clone()],
+                outline_descriptor_set,
+                outline_fs::ty::Outline {
+                    falloff: configuration.outline_falloff,
+                    opacity: if configuration.outline { 1.0 } else { 0.0 },
+                },
+            )
+            .unwrap()
+    }
+
+    /// Draw the level and entities seen from the camera following the player position
+    fn draw_world(
+        &mut self,
+        mut command_buffer_builder: AutoCommandBufferBuilder,
+        world: &World,
+        dynamic_state: &DynamicState,
+        viewport: &Viewport,
+        player_pos: &::na::Isometry3<f32>,
+    ) -> AutoCommandBufferBuilder {
+        let physic_world = world.read_resource::<::resource::PhysicWorld>();
+        let physic_bodies = world.read_storage::<::component::PhysicBody>();
+        let physic_sensors = world.read_storage::<::component::PhysicSensor>();
+
+        let view_trans = camera_view(player_pos);
+
+        let ship_position = view_trans * ::na::Point3::from_coordinates(player_pos.translation.vector);
+        let (ship_radius, outline_width) = {
+            let configuration = world.read_resource::<::configuration::Configuration>();
+            let outline_width = if configuration.outline { configuration.outline_width } else { 0.0 };
+            (configuration.ball_radius, outline_width)
+        };
+
+        let view = self.view_buffer_pool
+            .next(vs::ty::View {
+                view: view_trans.unwrap().into(),
+                ship: [ship_position[0], ship_position[1], ship_position[2], ship_radius],
+                ink: [outline_width, 0.0, 0.0, 0.0],
+            })
+            .unwrap();
+
+        let perspective_matrix = camera_perspective(viewport).unwrap();
+
+        let perspective = self.perspective_buffer_pool
+            .next(vs::ty::Perspective {
+                perspective: perspective_matrix.into(),
+            })
+            .unwrap();
+
+        // The skybox follows the orientation of the camera but not its position
+        let skybox_view: ::na::Transform3<f32> = ::na::Similarity3::look_at_rh(
+            &::na::Point3::origin(),
+            &::na::Point3::from_coordinates(player_pos.rotation * ::na::Vector3::x()),
+            &(player_pos.rotation * ::na::Vector3::z()),
+            1.0,
+        ).to_superset();
+
+        command_buffer_builder = command_buffer_builder
+            .draw(
+                self.skybox_pipeline.clone(),
+                dynamic_state.clone(),
+                vec![self.cuboid_vertex_buffer.clone()],
+                self.skybox_descriptor_set.clone(),
+                skybox_vs::ty::Camera {
+                    view_perspective: (perspective_matrix * skybox_view.unwrap()).into(),
+                },
+            )
+            .unwrap();
+
+        let camera_descriptor_set = Arc::new(
+            self.camera_descriptor_sets_pool
+                .next()
+                .add_buffer(perspective)
+                .unwrap()
+                .add_buffer(view)
+                .unwrap()
+                .build()
+                .unwrap(),
+        );
+
+        for tile in &world.read_resource::<::resource::Tiles>().0 {
+            let (ref texture_descriptor_set, ref vertex_buffer, color) =
+                self.tile_assets[&tile.size];
+
+            let position: ::na::Transform3<f32> = tile.position.to_superset();
+            let (width, height) = tile.size.size();
+            let half_extents = ::obj::tile_half_extents(width, height);
+
+            let model = self.model_buffer_pool
+                .next(vs::ty::Model {
+                    model: position.unwrap().into(),
+                    outline: [half_extents[0], half_extents[1], half_extents[2], 1.0],
+                })
+                .unwrap();
+
+            let model_descriptor_set = self.model_descriptor_sets_pool
+                .next()
+                .add_buffer(model)
+                .unwrap()
+                .build()
+                .unwrap();
+
+            command_buffer_builder = command_buffer_builder
+                .draw(
+                    self.pipeline.clone(),
+                    dynamic_state.clone(),
+                    vec![vertex_buffer.clone()],
+                    (
+                        camera_descriptor_set.clone(),
+                        model_descriptor_set,
+                        texture_descriptor_set.clone(),
+                    ),
+                    color,
+                )
+                .unwrap();
+        }
+
+        for tube in &world.read_resource::<::resource::Tubes>().0 {
+            let (ref texture_descriptor_set, ref vertex_buffer) = self.tube_assets[&tube.shape];
+
+            let position: ::na::Transform3<f32> = tube.position.to_superset();
+
+            let model = self.model_buffer_pool
+                .next(vs::ty::Model {
+                    model: position.unwrap().into(),
+                    outline: [0.0; 4],
+                })
+                .unwrap();
+
+            let model_descriptor_set = self.model_descriptor_sets_pool
+                .next()
+                .add_buffer(model)
+                .unwrap()
+                .build()
+                .unwrap();
+
+            command_buffer_builder = command_buffer_builder
+                .draw(
+                    self.pipeline.clone(),
+                    dynamic_state.clone(),
+                    vec![vertex_buffer.clone()],
+                    (
+                        camera_descriptor_set.clone(),
+                        model_descriptor_set,
+                        texture_descriptor_set.clone(),
+                    ),
+                    [1.0f32, 1.0, 1.0],
+                )
+                .unwrap();
+        }
+
+        // Draw trails as halos shrinking and fading to white
+        let ball_radius = world.read_resource::<::configuration::Configuration>().ball_radius;
+        let players_entities = world.read_resource::<::resource::PlayersEntities>();
+        for (entity, trail) in (&*world.entities(), &world.read_storage::<::component::Trail>()).join() {
+            // Only players and player killers have a trail
+            let color = match players_entities.iter().position(|e| *e == Some(entity)) {
+                Some(slot) => self.palette.rgb(PLAYER_COLORS[slot]),
+                None => KILLER_TRAIL_COLOR,
+            };
+
+            let len = trail.positions.len() as f32;
+            for (i, position) in trail.positions.iter().enumerate().skip(1) {
+                let fade = i as f32 / len;
+                let radius = ball_radius * (1.0 - fade);
+                let color = [
+                    color[0] + (1.0 - color[0]) * fade,
+                    color[1] + (1.0 - color[1]) * fade,
+                    color[2] + (1.0 - color[2]) * fade,
+                ];
+
+                let model: ::na::Transform3<f32> = ::na::Similarity3::from_parts(
+                    ::na::Translation::from_vector(*position),
+                    ::na::UnitQuaternion::identity(),
+                    radius,
+                ).to_superset();
+
+                let model = self.model_buffer_pool
+                    .next(vs::ty::Model {
+                        model: model.unwrap().into(),
+                        outline: [0.0; 4],
+                    })
+                    .unwrap();
+
+                let model_descriptor_set = self.model_descriptor_sets_pool
+                    .next()
+                    .add_buffer(model)
+                    .unwrap()
+                    .build()
+                    .unwrap();
+
+                command_buffer_builder = command_buffer_builder
+                    .draw(
+                        self.pipeline.clone(),
+                        dynamic_state.clone(),
+                        vec![self.ball_vertex_buffer.clone()],
+                        (
+                            camera_descriptor_set.clone(),
+                            model_descriptor_set,
+                            self.unlocal_texture_descriptor_set.clone(),
+                        ),
+                        color,
+                    )
+                    .unwrap();
+            }
+        }
+
+        // Draw ships with their thrust behind
+        let flight_controls = world.read_storage::<::component::FlightControl>();
+        let interpolation = world.read_resource::<::resource::Interpolation>();
+        for (entity, body, flight_control) in (&*world.entities(), &physic_bodies, &flight_controls).join() {
+            let slot = match players_entities.iter().position(|e| *e == Some(entity)) {
+                Some(slot) => slot,
+                None => continue,
+            };
+            let position = interpolation.position(body, &physic_world);
+
+            let ship: ::na::Transform3<f32> = ::na::Similarity3::from_isometry(
+                position,
+                ball_radius,
+            ).to_superset();
+
+            let thrust_length = 0.2 + 0.6 * flight_control.power.max(0.0).min(1.0);
+            let thrust: ::na::Transform3<f32> = ::na::Similarity3::from_isometry(
+                position * ::na::Translation3::new(-(0.8 + thrust_length) * ball_radius, 0.0, 0.0),
+                ball_radius,
+            ).to_superset();
+            let thrust_trans = ::na::Matrix4::from_diagonal(&::na::Vector4::new(
+                thrust_length,
+                0.15,
+                0.15,
+                1.0,
+            ));
+
+            let parts = [
+                (
+                    ship.unwrap(),
+                    self.ship_vertex_buffer.clone(),
+                    self.palette.rgb(PLAYER_COLORS[slot]),
+                ),
+                (
+                    thrust.unwrap() * thrust_trans,
+                    self.ball_vertex_buffer.clone(),
+                    THRUST_COLOR,
+                ),
+            ];
+
+            for &(ref model, ref vertex_buffer, color) in &parts {
+                let model = self.model_buffer_pool
+                    .next(vs::ty::Model {
+                        model: (*model).into(),
+                        outline: [0.0; 4],
+                    })
+                    .unwrap();
+
+                let model_descriptor_set = self.model_descriptor_sets_pool
+                    .next()
+                    .add_buffer(model)
+                    .unwrap()
+                    .build()
+                    .unwrap();
+
+                command_buffer_builder = command_buffer_builder
+                    .draw(
+                        self.pipeline.clone(),
+                        dynamic_state.clone(),
+                        vec![vertex_buffer.clone()],
+                        (
+                            camera_descriptor_set.clone(),
+                            model_descriptor_set,
+                            self.unlocal_texture_descriptor_set.clone(),
+                        ),
+                        color,
+                    )
+                    .unwrap();
+            }
+        }
+
+        // Draw balls of rockets, mines and targets, players are drawn as ships.
+        // In physic debug mode every shape is drawn colored by its group.
+        let physic_debug = world.read_resource::<::debug::Overlay>().physic;
+        let players = world.read_storage::<::component::Player>();
+        let bodies = (&physic_bodies, !&players).join().map(|(body, ())| {
+            let position = interpolation.position(body, &physic_world);
+            let body = body.get(&physic_world);
+            let group = ::entity::Group::of(body.collision_groups().as_collision_groups());
+            (position, body.shape(), group)
+        });
+        let sensors = physic_sensors.join().map(|sensor| {
+            let position = sensor.position(&physic_world);
+            let sensor = sensor.get(&physic_world);
+            let group = ::entity::Group::of(sensor.collision_groups().as_collision_groups());
+            (position, sensor.shape(), group)
+        });
+
+        for (position, shape, group) in bodies.chain(sensors) {
+            let (vertex_buffer, half_extents) = if let Some(shape) = shape.as_shape::<::ncollide::shape::Ball<f32>>() {
+                (self.ball_vertex_buffer.clone(), ::na::Vector3::from_element(shape.radius()))
+            } else if !physic_debug {
+                continue;
+            } else if let Some(shape) = shape.as_shape::<::ncollide::shape::Cylinder<f32>>() {
+                (
+                    self.cylinder_vertex_buffer.clone(),
+                    ::na::Vector3::new(shape.radius(), shape.half_height(), shape.radius()),
+                )
+            } else if let Some(shape) = shape.as_shape::<::ncollide::shape::Cuboid<::na::Vector3<f32>>>() {
+                (self.cuboid_vertex_buffer.clone(), shape.half_extents().clone())
+            } else {
+                continue;
+            };
+
+            let color = if physic_debug {
+                group.map_or(UNKNOWN_GROUP_COLOR, |group| GROUP_COLORS[group as usize])
+            } else {
+                [1.0, 1.0, 1.0]
+            };
+
+            let primitive_trans = ::na::Matrix4::from_diagonal(&::na::Vector4::new(
+                half_extents[0],
+                half_extents[1],
+                half_extents[2],
+                1.0,
+            ));
+
+            let position: ::na::Transform3<f32> = position.to_superset();
+
+            let model = self.model_buffer_pool
+                .next(vs::ty::Model {
+                    model: (position.unwrap() * primitive_trans).into(),
+                    outline: [0.0; 4],
+                })
+                .unwrap();
+
+            let model_descriptor_set = self.model_descriptor_sets_pool
+                .next()
+                .add_buffer(model)
+                .unwrap()
+                .build()
+                .unwrap();
+
+            command_buffer_builder = command_buffer_builder
+                .draw(
+                    self.pipeline.clone(),
+                    dynamic_state.clone(),
+                    vec![vertex_buffer],
+                    (
+                        camera_descriptor_set.clone(),
+                        model_descriptor_set,
+                        self.unlocal_texture_descriptor_set.clone(),
+                    ),
+                    color,
+                )
+                .unwrap();
+        }
+
+        command_buffer_builder
+    }
+
+    fn build_command_buffer(
+        &mut self,
+        image_num: usize,
+        world: &mut World,
+        game_state: Box<::game_state::GameState>,
+    ) -> (
+        AutoCommandBuffer<StandardCommandPoolAlloc>,
+        Box<::game_state::GameState>,
+        Box<::vulkano::sync::GpuFuture>,
+    ) {
+        let dimensions = self.swapchain().dimensions();
+
+        let screen_dynamic_state = DynamicState {
+            viewports: Some(vec![
+                Viewport {
+                    origin: [0.0, 0.0],
+                    dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+                    depth_range: 0.0..1.0,
+                },
+            ]),
+            ..DynamicState::none()
+        };
+
+        let next_game_state = game_state.update_draw_ui(world);
+        let mut text_buffers = self.build_text_buffers(&mut world.write_resource(), *world.read_resource(), dimensions);
+
+        let mut command_buffer_builder = AutoCommandBufferBuilder::primary_one_time_submit(
+            self.device.clone(),
+            self.queue.family(),
         ).unwrap();
 
         // TODO: log when needs update: should be at each text change not always !!
@@ -882,14 +1977,14 @@ impl Graphics {
             .begin_render_pass(
                 self.framebuffers[image_num].clone(),
                 false,
-                vec![[1.0, 1.0, 1.0, 1.0].into(), 1.0.into()],
+                CustomRenderPassDesc::clear_values(),
             )
             .unwrap();
 
         let mut next_player_position_memory = [None; 3];
+        let mut players_dynamic_state = vec![];
         // Draw world
         for player in 0..world.read_resource::<::resource::Mode>().number_of_player() {
-            let player_entities = world.read_resource::<::resource::PlayersEntities>();
             let mode = world.read_resource::<::resource::Mode>();
             let viewport = mode.viewport_for_player(player, dimensions);
             let dynamic_state = DynamicState {
@@ -897,279 +1992,117 @@ impl Graphics {
                 ..DynamicState::none()
             };
 
-            let physic_world = world.read_resource::<::resource::PhysicWorld>();
-            let physic_bodies = world.read_storage::<::component::PhysicBody>();
-            let physic_sensors = world.read_storage::<::component::PhysicSensor>();
-
-            let player_pos = player_entities[player]
-                .and_then(|entity| physic_bodies.get(entity))
-                .map(|body| body.get(&physic_world).position().clone())
-                .or(self.player_position_memory[player]);
+            let player_pos = self.player_position(world, player);
 
             if let Some(player_pos) = player_pos {
                 next_player_position_memory[player] = Some(player_pos);
-                let view_trans: ::na::Transform3<f32> = ::na::Similarity3::look_at_rh(
-                    &::na::Point3::from_coordinates(
-                        player_pos.translation.vector
-                            + player_pos.rotation * ::na::Vector3::new(-1.0, 0.0, 0.2),
-                    ),
-                    &::na::Point3::from_coordinates(
-                        player_pos.translation.vector
-                            + player_pos.rotation * ::na::Vector3::new(0.0, 0.0, 0.2),
-                    ),
-                    &(player_pos.rotation * ::na::Vector3::z()),
-                    1.0,
-                ).to_superset();
-
-                let view = self.view_buffer_pool
-                    .next(vs::ty::View {
-                        view: view_trans.unwrap().into(),
-                    })
-                    .unwrap();
-
-                let perspective = self.perspective_buffer_pool
-                    .next(vs::ty::Perspective {
-                        perspective: ::na::Perspective3::new(
-                            viewport.dimensions[0] as f32 / viewport.dimensions[1] as f32,
-                            ::std::f32::consts::FRAC_PI_3,
-                            0.01,
-                            100.0,
-                        ).unwrap()
-                            .into(),
-                    })
-                    .unwrap();
-
-                let camera_descriptor_set = Arc::new(
-                    self.camera_descriptor_sets_pool
-                        .next()
-                        .add_buffer(perspective)
-                        .unwrap()
-                        .add_buffer(view)
-                        .unwrap()
-                        .build()
-                        .unwrap(),
-                );
-
-                for tile in &world.read_resource::<::resource::Tiles>().0 {
-                    let (ref texture_descriptor_set, ref vertex_buffer, color) =
-                        self.tile_assets[&tile.size];
-
-                    let position: ::na::Transform3<f32> = tile.position.to_superset();
-
-                    let model = self.model_buffer_pool
-                        .next(vs::ty::Model {
-                            model: position.unwrap().into(),
-                        })
-                        .unwrap();
-
-                    let model_descriptor_set = self.model_descriptor_sets_pool
-                        .next()
-                        .add_buffer(model)
-                        .unwrap()
-                        .build()
-                        .unwrap();
-
-                    command_buffer_builder = command_buffer_builder
-                        .draw(
-                            self.pipeline.clone(),
-                            dynamic_state.clone(),
-                            vec![vertex_buffer.clone()],
-                            (
-                                camera_descriptor_set.clone(),
-                                model_descriptor_set,
-                                texture_descriptor_set.clone(),
-                            ),
-                            color,
-                        )
-                        .unwrap();
-                }
-
-                for tube in &world.read_resource::<::resource::Tubes>().0 {
-                    let (ref texture_descriptor_set, ref vertex_buffer) = self.tube_assets[&tube.shape];
-
-                    let position: ::na::Transform3<f32> = tube.position.to_superset();
+                command_buffer_builder = self.draw_world(command_buffer_builder, world, &dynamic_state, &viewport, &player_pos);
+            }
+            players_dynamic_state.push((viewport, dynamic_state));
+        }
+        self.player_position_memory = next_player_position_memory;
 
-                    let model = self.model_buffer_pool
-                        .next(vs::ty::Model {
-                            model: position.unwrap().into(),
-                        })
-                        .unwrap();
+        let outline_descriptor_set = self.outline_descriptor_set.clone()
+            .expect("outline attachments are created with the swapchain");
+        command_buffer_builder = self.draw_outline(
+            command_buffer_builder,
+            world,
+            outline_descriptor_set,
+            &screen_dynamic_state,
+        );
 
-                    let model_descriptor_set = self.model_descriptor_sets_pool
-                        .next()
-                        .add_buffer(model)
-                        .unwrap()
-                        .build()
-                        .unwrap();
-
-                    command_buffer_builder = command_buffer_builder
-                        .draw(
-                            self.pipeline.clone(),
-                            dynamic_state.clone(),
-                            vec![vertex_buffer.clone()],
-                            (
-                                camera_descriptor_set.clone(),
-                                model_descriptor_set,
-                                texture_descriptor_set.clone(),
-                            ),
-                            [1.0f32, 1.0, 1.0],
-                        )
-                        .unwrap();
+        // Draw text and huds over the outlined scene
+        let physic_lines = ::debug::physic_lines(world);
+        for (player, (viewport, dynamic_state)) in players_dynamic_state.into_iter().enumerate() {
+            if !physic_lines.is_empty() {
+                if let Some(player_pos) = self.player_position_memory[player] {
+                    let view_perspective = camera_perspective(&viewport).unwrap() * camera_view(&player_pos).unwrap();
+                    command_buffer_builder = self.draw_lines(
+                        command_buffer_builder,
+                        &dynamic_state,
+                        &view_perspective,
+                        physic_lines.clone(),
+                    );
                 }
-
-                // Draw physic world
-                if true {
-                    let bodies = physic_bodies.join().map(|body| {
-                        let body = body.get(&physic_world);
-                        let shape = body.shape();
-                        (body.position(), shape)
-                    });
-                    // TODO: this is for debug only as sensor relative position are not always absolute
-                    let sensors = physic_sensors.join().map(|sensor| {
-                        let sensor = sensor.get(&physic_world);
-                        let shape = sensor.shape();
-                        (sensor.relative_position(), shape)
-                    });
-
-                    for (position, shape) in bodies.chain(sensors) {
-                        if let Some(shape) = shape.as_shape::<::ncollide::shape::Ball<f32>>() {
-                            let primitive_trans = ::na::Matrix4::from_diagonal(&::na::Vector4::new(
-                                shape.radius(),
-                                shape.radius(),
-                                shape.radius(),
-                                1.0,
-                            ));
-
-                            let position: ::na::Transform3<f32> = position.to_superset();
-
-                            let model = self.model_buffer_pool
-                                .next(vs::ty::Model {
-                                    model: (position.unwrap() * primitive_trans).into(),
-                                })
-                                .unwrap();
-
-                            let model_descriptor_set = self.model_descriptor_sets_pool
-                                .next()
-                                .add_buffer(model)
-                                .unwrap()
-                                .build()
-                                .unwrap();
-
-                            command_buffer_builder = command_buffer_builder
-                                .draw(
-                                    self.pipeline.clone(),
-                                    dynamic_state.clone(),
-                                    vec![self.ball_vertex_buffer.clone()],
-                                    (
-                                        camera_descriptor_set.clone(),
-                                        model_descriptor_set,
-                                        self.unlocal_texture_descriptor_set.clone(),
-                                    ),
-                                    [1.0f32, 1.0, 1.0],
-                                )
-                                .unwrap();
-                        } else if let Some(shape) = shape.as_shape::<::ncollide::shape::Cylinder<f32>>()
-                        {
-                            let primitive_trans = ::na::Matrix4::from_diagonal(&::na::Vector4::new(
-                                shape.radius(),
-                                shape.half_height(),
-                                shape.radius(),
-                                1.0,
-                            ));
-
-                            let position: ::na::Transform3<f32> = position.to_superset();
-
-                            let model = self.model_buffer_pool
-                                .next(vs::ty::Model {
-                                    model: (position.unwrap() * primitive_trans).into(),
-                                })
-                                .unwrap();
-
-                            let model_descriptor_set = self.model_descriptor_sets_pool
-                                .next()
-                                .add_buffer(model)
-                                .unwrap()
-                                .build()
-                                .unwrap();
-
-                            command_buffer_builder = command_buffer_builder
-                                .draw(
-                                    self.pipeline.clone(),
-                                    dynamic_state.clone(),
-                                    vec![self.cylinder_vertex_buffer.clone()],
-                                    (
-                                        camera_descriptor_set.clone(),
-                                        model_descriptor_set,
-                                        self.unlocal_texture_descriptor_set.clone(),
-                                    ),
-                                    [1.0f32, 1.0, 1.0],
-                                )
-                                .unwrap();
-                        } else if let Some(shape) =
-                            shape.as_shape::<::ncollide::shape::Cuboid<::na::Vector3<f32>>>()
-                        {
-                            let radius = shape.half_extents();
-                            let primitive_trans = ::na::Matrix4::from_diagonal(&::na::Vector4::new(
-                                radius[0],
-                                radius[1],
-                                radius[2],
-                                1.0,
-                            ));
-
-                            let position: ::na::Transform3<f32> = position.to_superset();
-
-                            let model = self.model_buffer_pool
-                                .next(vs::ty::Model {
-                                    model: (position.unwrap() * primitive_trans).into(),
-                                })
-                                .unwrap();
-
-                            let model_descriptor_set = self.model_descriptor_sets_pool
-                                .next()
-                                .add_buffer(model)
-                                .unwrap()
-                                .build()
-                                .unwrap();
-
-                            command_buffer_builder = command_buffer_builder
-                                .draw(
-                                    self.pipeline.clone(),
-                                    dynamic_state.clone(),
-                                    vec![self.cuboid_vertex_buffer.clone()],
-                                    (
-                                        camera_descriptor_set.clone(),
-                                        model_descriptor_set,
-                                        self.unlocal_texture_descriptor_set.clone(),
-                                    ),
-                                    [1.0f32, 1.0, 1.0],
-                                )
-                                .unwrap();
-                        }
-                    }
+            }
+            if let Some(player_pos) = self.player_position_memory[player] {
+                command_buffer_builder = self.draw_minimap(command_buffer_builder, world, player, &player_pos, &viewport);
+            }
+            // Shapes of the hud are drawn along its text
+            if let Some(buffer) = text_buffers.huds[player].take() {
+                if let Some(player_pos) = self.player_position_memory[player] {
+                    command_buffer_builder = command_buffer_builder.draw(
+                        self.hud_pipeline.clone(),
+                        dynamic_state.clone(),
+                        vec![self.hud_buffer(world, player, &player_pos, &viewport)],
+                        (),
+                        (),
+                    ).unwrap();
                 }
+                command_buffer_builder = command_buffer_builder.draw(
+                    self.text_pipeline.clone(),
+                    dynamic_state.clone(),
+                    vec![buffer],
+                    self.cache_image_set.clone(),
+                    (),
+                ).unwrap();
             }
             if let Some(buffer) = text_buffers.players[player].take() {
                 command_buffer_builder = command_buffer_builder.draw(
                     self.text_pipeline.clone(),
-                    dynamic_state.clone(),
+                    dynamic_state,
                     vec![buffer],
                     self.cache_image_set.clone(),
                     (),
                 ).unwrap();
             }
         }
-        self.player_position_memory = next_player_position_memory;
 
         if let Some(buffer) = text_buffers.global.take() {
             command_buffer_builder = command_buffer_builder.draw(
                 self.text_pipeline.clone(),
-                screen_dynamic_state,
+                screen_dynamic_state.clone(),
                 vec![buffer.clone()],
                 self.cache_image_set.clone(),
                 (),
             ).unwrap();
         }
 
+        if let Some(buffer) = text_buffers.notification.take() {
+            command_buffer_builder = command_buffer_builder.draw(
+                self.text_pipeline.clone(),
+                screen_dynamic_state.clone(),
+                vec![buffer],
+                self.cache_image_set.clone(),
+                (),
+            ).unwrap();
+        }
+
+        if let Some(vertices) = ::debug::graph(world) {
+            let buffer = CpuAccessibleBuffer::from_iter(
+                self.device.clone(),
+                BufferUsage::vertex_buffer(),
+                vertices.into_iter(),
+            ).unwrap();
+            command_buffer_builder = command_buffer_builder.draw(
+                self.hud_pipeline.clone(),
+                screen_dynamic_state.clone(),
+                vec![buffer],
+                (),
+                (),
+            ).unwrap();
+        }
+
+        if let Some(buffer) = text_buffers.debug.take() {
+            command_buffer_builder = command_buffer_builder.draw(
+                self.text_pipeline.clone(),
+                screen_dynamic_state,
+                vec![buffer],
+                self.cache_image_set.clone(),
+                (),
+            ).unwrap();
+        }
+
         let command = command_buffer_builder
             .end_render_pass()
             .unwrap()
@@ -1180,6 +2113,31 @@ impl Graphics {
     }
 }
 
+/// Camera of a player, behind and above its ship
+fn camera_view(player_pos: &::na::Isometry3<f32>) -> ::na::Transform3<f32> {
+    ::na::Similarity3::look_at_rh(
+        &::na::Point3::from_coordinates(
+            player_pos.translation.vector
+                + player_pos.rotation * ::na::Vector3::new(-1.0, 0.0, 0.2),
+        ),
+        &::na::Point3::from_coordinates(
+            player_pos.translation.vector
+                + player_pos.rotation * ::na::Vector3::new(0.0, 0.0, 0.2),
+        ),
+        &(player_pos.rotation * ::na::Vector3::z()),
+        1.0,
+    ).to_superset()
+}
+
+fn camera_perspective(viewport: &Viewport) -> ::na::Perspective3<f32> {
+    ::na::Perspective3::new(
+        viewport.dimensions[0] as f32 / viewport.dimensions[1] as f32,
+        ::std::f32::consts::FRAC_PI_3,
+        0.01,
+        FAR_PLANE,
+    )
+}
+
 mod vs {
     #[derive(VulkanoShader)]
     #[ty = "vertex"]
@@ -1191,22 +2149,33 @@ layout(location = 0) in vec3 position;
 layout(location = 1) in vec2 tex_coords;
 
 layout(location = 0) out vec2 v_tex_coords;
+layout(location = 1) out vec3 v_view_position;
+layout(location = 2) out vec3 v_model_position;
 
 layout(set = 0, binding = 0) uniform Perspective {
     mat4 perspective;
 } perspective;
+// ship: position of the followed ship in view space and its radius
+// ink: width of outlines in pixels, zero if disabled
 layout(set = 0, binding = 1) uniform View {
     mat4 view;
+    vec4 ship;
+    vec4 ink;
 } view;
+// outline: half extents of the cuboid whose edges are outlined, w is one if outlined
 layout(set = 1, binding = 0) uniform Model {
     mat4 model;
+    vec4 outline;
 } model;
 
 void main() {
     // TODO: make perspective/view multiplication on the cpu
-    gl_Position = perspective.perspective * view.view * model.model * vec4(position, 1.0);
+    vec4 view_position = view.view * model.model * vec4(position, 1.0);
+    gl_Position = perspective.perspective * view_position;
     gl_Position.y = - gl_Position.y;
     v_tex_coords = tex_coords;
+    v_view_position = view_position.xyz;
+    v_model_position = position;
 }
     "]
     struct _Dummy;
@@ -1224,15 +2193,156 @@ layout(push_constant) uniform Color {
 } color;
 
 layout(location = 0) in vec2 v_tex_coords;
+layout(location = 1) in vec3 v_view_position;
+layout(location = 2) in vec3 v_model_position;
 
 layout(location = 0) out vec4 out_color;
+// Coverage of outlines and distance to the camera
+layout(location = 1) out vec4 out_ink;
 
+layout(set = 0, binding = 1) uniform View {
+    mat4 view;
+    vec4 ship;
+    vec4 ink;
+} view;
+layout(set = 1, binding = 0) uniform Model {
+    mat4 model;
+    vec4 outline;
+} model;
 layout(set = 2, binding = 0) uniform sampler2D tex;
 
+// Distance to the camera-ship axis under which geometry is faded
+const float AXIS_FADE = 0.4;
+// Distance to the camera under which geometry is faded
+const float NEAR_FADE = 0.2;
+
+const float BAYER[16] = float[](
+     0.0,  8.0,  2.0, 10.0,
+    12.0,  4.0, 14.0,  6.0,
+     3.0, 11.0,  1.0,  9.0,
+    15.0,  7.0, 13.0,  5.0
+);
+
 void main() {
+    // Fade geometry between the camera and the ship using ordered dithering
+    // so no sorting is needed
+    float camera_distance = length(v_view_position);
+    float opacity = smoothstep(0.0, NEAR_FADE, camera_distance);
+    if (camera_distance < length(view.ship.xyz) - view.ship.w) {
+        vec3 axis = normalize(view.ship.xyz);
+        float axis_distance = length(v_view_position - dot(v_view_position, axis) * axis);
+        opacity = min(opacity, mix(0.2, 1.0, smoothstep(0.0, AXIS_FADE, axis_distance)));
+    }
+    ivec2 texel = ivec2(gl_FragCoord.xy) % 4;
+    if (opacity < (BAYER[texel.y * 4 + texel.x] + 0.5) / 16.0) {
+        discard;
+    }
+
     vec3 black = vec3(0.0, 0.0, 0.0);
     float grey = texture(tex, v_tex_coords).r;
     out_color = vec4(black*grey + color.color*(1.0 - grey), 1.0);
+
+    // Distance in pixels to the edges of the face: the closest plane is the
+    // face itself so the edges are the second closest ones
+    vec3 plane_distance = model.outline.xyz - abs(v_model_position);
+    vec3 pixel_distance = plane_distance / max(fwidth(v_model_position), vec3(0.000001));
+    float edge_distance;
+    if (plane_distance.x <= plane_distance.y && plane_distance.x <= plane_distance.z) {
+        edge_distance = min(pixel_distance.y, pixel_distance.z);
+    } else if (plane_distance.y <= plane_distance.z) {
+        edge_distance = min(pixel_distance.x, pixel_distance.z);
+    } else {
+        edge_distance = min(pixel_distance.x, pixel_distance.y);
+    }
+    float ink = 1.0 - smoothstep(view.ink.x - 1.0, view.ink.x, edge_distance);
+    out_ink = vec4(ink * model.outline.w, camera_distance, 0.0, 1.0);
+}
+    "]
+    struct _Dummy;
+}
+
+mod skybox_vs {
+    #[derive(VulkanoShader)]
+    #[ty = "vertex"]
+    #[src = "
+#version 450
+
+layout(location = 0) in vec3 position;
+layout(location = 0) out vec3 v_direction;
+
+layout(push_constant) uniform Camera {
+    mat4 view_perspective;
+} camera;
+
+void main() {
+    v_direction = position;
+    // Always on the far plane
+    gl_Position = (camera.view_perspective * vec4(position, 1.0)).xyww;
+    gl_Position.y = - gl_Position.y;
+}
+    "]
+    struct _Dummy;
+}
+
+mod skybox_fs {
+    #[derive(VulkanoShader)]
+    #[ty = "fragment"]
+    #[src = "
+#version 450
+
+layout(location = 0) in vec3 v_direction;
+layout(location = 0) out vec4 out_color;
+layout(location = 1) out vec4 out_ink;
+
+layout(set = 0, binding = 0) uniform samplerCube sky;
+
+void main() {
+    out_color = vec4(vec3(texture(sky, v_direction).r), 1.0);
+    // The sky is never outlined
+    out_ink = vec4(0.0, 0.0, 0.0, 1.0);
+}
+    "]
+    struct _Dummy;
+}
+
+mod outline_vs {
+    #[derive(VulkanoShader)]
+    #[ty = "vertex"]
+    #[src = "
+#version 450
+
+layout(location = 0) in vec2 position;
+
+void main() {
+    gl_Position = vec4(position, 0.0, 1.0);
+}
+    "]
+    struct _Dummy;
+}
+
+mod outline_fs {
+    #[derive(VulkanoShader)]
+    #[ty = "fragment"]
+    #[src = "
+#version 450
+
+layout(location = 0) out vec4 out_color;
+
+layout(input_attachment_index = 0, set = 0, binding = 0) uniform subpassInput scene;
+layout(input_attachment_index = 1, set = 0, binding = 1) uniform subpassInput ink;
+
+// falloff: distance at which outlines have disappeared
+// opacity: zero to disable outlines
+layout(push_constant) uniform Outline {
+    float falloff;
+    float opacity;
+} outline;
+
+void main() {
+    vec3 color = subpassLoad(scene).rgb;
+    vec2 ink = subpassLoad(ink).rg;
+    float strength = ink.x * outline.opacity * (1.0 - smoothstep(0.0, outline.falloff, ink.y));
+    out_color = vec4(mix(color, vec3(0.0), strength), 1.0);
 }
     "]
     struct _Dummy;
@@ -1274,24 +2384,110 @@ void main() {
     struct _Dummy;
 }
 
+mod hud_vs {
+    #[derive(VulkanoShader)]
+    #[ty = "vertex"]
+    #[src = "
+#version 450
+
+layout(location = 0) in vec2 position;
+layout(location = 1) in vec4 color;
+layout(location = 0) out vec4 v_color;
+
+void main() {
+    gl_Position = vec4(position, 0.0, 1.0);
+    v_color = color;
+}
+    "]
+    struct _Dummy;
+}
+
+mod hud_fs {
+    #[derive(VulkanoShader)]
+    #[ty = "fragment"]
+    #[src = "
+#version 450
+
+layout(location = 0) in vec4 v_color;
+layout(location = 0) out vec4 f_color;
+
+void main() {
+    f_color = v_color;
+}
+    "]
+    struct _Dummy;
+}
+
+mod line_vs {
+    #[derive(VulkanoShader)]
+    #[ty = "vertex"]
+    #[src = "
+#version 450
+
+layout(location = 0) in vec3 position;
+layout(location = 0) out vec4 v_color;
+
+layout(push_constant) uniform Camera {
+    mat4 view_perspective;
+    vec4 color;
+} camera;
+
+void main() {
+    gl_Position = camera.view_perspective * vec4(position, 1.0);
+    gl_Position.y = - gl_Position.y;
+    v_color = camera.color;
+}
+    "]
+    struct _Dummy;
+}
+
+mod line_fs {
+    #[derive(VulkanoShader)]
+    #[ty = "fragment"]
+    #[src = "
+#version 450
+
+layout(location = 0) in vec4 v_color;
+layout(location = 0) out vec4 f_color;
+
+void main() {
+    f_color = v_color;
+}
+    "]
+    struct _Dummy;
+}
+
+/// The world is drawn in the scene and ink attachments in the first subpass
+/// and composited with its outlines into the image in the second one
 pub struct CustomRenderPassDesc {
     swapchain_image_format: Format,
 }
 
+impl CustomRenderPassDesc {
+    fn clear_values() -> Vec<ClearValue> {
+        vec![
+            ClearValue::None,
+            1.0.into(),
+            [1.0, 1.0, 1.0, 1.0].into(),
+            [0.0, FAR_PLANE, 0.0, 0.0].into(),
+        ]
+    }
+}
+
 unsafe impl RenderPassDesc for CustomRenderPassDesc {
     #[inline]
     fn num_attachments(&self) -> usize {
-        2
+        4
     }
 
     #[inline]
     fn attachment_desc(&self, id: usize) -> Option<LayoutAttachmentDescription> {
         match id {
-            // Colors
+            // Colors, entirely covered by the outline subpass
             0 => Some(LayoutAttachmentDescription {
                 format: self.swapchain_image_format,
                 samples: 1,
-                load: LoadOp::Clear,
+                load: LoadOp::DontCare,
                 store: StoreOp::Store,
                 stencil_load: LoadOp::Clear,
                 stencil_store: StoreOp::Store,
@@ -1309,37 +2505,94 @@ unsafe impl RenderPassDesc for CustomRenderPassDesc {
                 initial_layout: ImageLayout::Undefined,
                 final_layout: ImageLayout::DepthStencilAttachmentOptimal,
             }),
+            // Scene
+            2 => Some(LayoutAttachmentDescription {
+                format: SCENE_FORMAT,
+                samples: 1,
+                load: LoadOp::Clear,
+                store: StoreOp::DontCare,
+                stencil_load: LoadOp::Clear,
+                stencil_store: StoreOp::DontCare,
+                initial_layout: ImageLayout::Undefined,
+                final_layout: ImageLayout::ShaderReadOnlyOptimal,
+            }),
+            // Ink: outline coverage and distance to the camera
+            3 => Some(LayoutAttachmentDescription {
+                format: INK_FORMAT,
+                samples: 1,
+                load: LoadOp::Clear,
+                store: StoreOp::DontCare,
+                stencil_load: LoadOp::Clear,
+                stencil_store: StoreOp::DontCare,
+                initial_layout: ImageLayout::Undefined,
+                final_layout: ImageLayout::ShaderReadOnlyOptimal,
+            }),
             _ => None,
         }
     }
 
     #[inline]
     fn num_subpasses(&self) -> usize {
-        1
+        2
     }
 
     #[inline]
     fn subpass_desc(&self, id: usize) -> Option<LayoutPassDescription> {
         match id {
             0 => Some(LayoutPassDescription {
-                color_attachments: vec![(0, ImageLayout::ColorAttachmentOptimal)],
+                color_attachments: vec![
+                    (2, ImageLayout::ColorAttachmentOptimal),
+                    (3, ImageLayout::ColorAttachmentOptimal),
+                ],
                 depth_stencil: Some((1, ImageLayout::DepthStencilAttachmentOptimal)),
                 input_attachments: vec![],
                 resolve_attachments: vec![],
                 preserve_attachments: vec![],
             }),
+            1 => Some(LayoutPassDescription {
+                color_attachments: vec![(0, ImageLayout::ColorAttachmentOptimal)],
+                depth_stencil: None,
+                input_attachments: vec![
+                    (2, ImageLayout::ShaderReadOnlyOptimal),
+                    (3, ImageLayout::ShaderReadOnlyOptimal),
+                ],
+                resolve_attachments: vec![],
+                preserve_attachments: vec![],
+            }),
             _ => None,
         }
     }
 
     #[inline]
     fn num_dependencies(&self) -> usize {
-        0
+        1
     }
 
     #[inline]
     fn dependency_desc(&self, id: usize) -> Option<LayoutPassDependencyDescription> {
         match id {
+            // The outline subpass reads what the first one wrote
+            0 => Some(LayoutPassDependencyDescription {
+                source_subpass: 0,
+                destination_subpass: 1,
+                src_stages: PipelineStages {
+                    color_attachment_output: true,
+                    ..PipelineStages::none()
+                },
+                dst_stages: PipelineStages {
+                    fragment_shader: true,
+                    ..PipelineStages::none()
+                },
+                src_access: AccessFlagBits {
+                    color_attachment_write: true,
+                    ..AccessFlagBits::none()
+                },
+                dst_access: AccessFlagBits {
+                    input_attachment_read: true,
+                    ..AccessFlagBits::none()
+                },
+                by_region: true,
+            }),
             _ => None,
         }
     }
@@ -1351,3 +2604,69 @@ unsafe impl RenderPassDescClearValues<Vec<ClearValue>> for CustomRenderPassDesc
         Box::new(values.into_iter())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use vulkano::instance::{Instance, InstanceExtensions};
+
+    const DIMENSIONS: [u32; 2] = [320, 180];
+    /// Maximal difference of a channel for pixels to be considered equal
+    const CHANNEL_TOLERANCE: i16 = 8;
+    /// Maximal proportion of pixels that can differ from the reference
+    const PIXEL_TOLERANCE: f32 = 0.01;
+
+    /// Compare to the reference in tests/golden, written instead if UPDATE_GOLDEN is set
+    fn assert_golden(image: &::image::RgbaImage, name: &str) {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden").join(name);
+        if ::std::env::var_os("UPDATE_GOLDEN").is_some() {
+            ::std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            image.save(&path).unwrap();
+            return;
+        }
+
+        let reference = ::image::open(&path)
+            .unwrap_or_else(|e| panic!("Failed to open {}: {}, run with UPDATE_GOLDEN=1 to create it", path.display(), e))
+            .to_rgba();
+        assert_eq!(reference.dimensions(), image.dimensions());
+
+        let different = reference.pixels()
+            .zip(image.pixels())
+            .filter(|&(a, b)| {
+                a.data.iter()
+                    .zip(b.data.iter())
+                    .any(|(&a, &b)| (a as i16 - b as i16).abs() > CHANNEL_TOLERANCE)
+            })
+            .count();
+        let proportion = different as f32 / (image.width() * image.height()) as f32;
+        assert!(
+            proportion <= PIXEL_TOLERANCE,
+            "{:.2}% of the pixels differ from {}",
+            proportion * 100.0,
+            path.display(),
+        );
+    }
+
+    /// Only the skybox is drawn as other assets are randomly generated.
+    ///
+    /// Needs a Vulkan driver, a software one is enough. The reference is generated with
+    /// `UPDATE_GOLDEN=1 cargo test -- --ignored` before enabling it in CI.
+    #[test]
+    #[ignore]
+    fn golden_empty_level() {
+        let configuration = ::configuration::Configuration::built_in().unwrap();
+        let instance = Instance::new(None, &InstanceExtensions::none(), None).unwrap();
+        let mut graphics = super::Graphics::new_headless(&instance, None, &configuration);
+
+        let mut world = ::create_world(configuration);
+        let maze = ::maze::Maze::new_rectangle(::na::Vector3::new(5, 5, 5));
+        ::level::LevelBuilder::default().build_structure(&maze, vec![], vec![], &mut world);
+        graphics.player_position_memory[0] = Some(::na::Isometry3::new(
+            ::na::Vector3::new(2.0, 2.0, 2.0),
+            ::na::Vector3::new(0.3, 0.6, 0.0),
+        ));
+
+        let image = graphics.render_offscreen(&world, DIMENSIONS);
+        assert_golden(&image, "empty_level.png");
+    }
+}
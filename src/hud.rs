@@ -0,0 +1,128 @@
+use specs::{Join, World};
+
+/// Size of the text relative to the text of menus
+pub const TEXT_SCALE: f32 = 0.6;
+/// Rockets closer than this distance are indicated
+const THREAT_DISTANCE: f32 = 8.0;
+/// Position of the indicators relative to the border of the viewport
+const INDICATOR_MARGIN: f32 = 0.9;
+const INDICATOR_SIZE: f32 = 0.05;
+const TARGET_COLOR: [f32; 4] = [0.35, 0.75, 0.4, 0.9];
+const THREAT_COLOR: [f32; 4] = [0.9, 0.35, 0.3, 0.9];
+const BOOST_COLOR: [f32; 4] = [1.0, 0.6, 0.2, 0.9];
+const BOOST_BACKGROUND_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.2];
+/// Bottom left corner and size of the boost bar
+const BOOST_ORIGIN: [f32; 2] = [-0.95, 0.95];
+const BOOST_SIZE: [f32; 2] = [0.5, 0.04];
+
+/// Vertex in the normalized coordinates of the viewport of a player
+#[derive(Debug, Clone)]
+pub struct HudVertex {
+    position: [f32; 2],
+    color: [f32; 4],
+}
+impl_vertex!(HudVertex, position, color);
+
+/// Text lines of the hud of the player
+pub fn lines(world: &World, player: usize) -> Vec<String> {
+    let statistics = world.read_resource::<::resource::Statistics>();
+    let targets = world.read_storage::<::component::Target>().join().count();
+    let elapsed = statistics.elapsed as usize;
+
+    vec![
+        world.read_resource::<::resource::Save>().player_name(player),
+        format!("Targets: {}", targets),
+        format!("Time: {}:{:02}", elapsed / 60, elapsed % 60),
+        format!("Deaths: {}", statistics.deaths[player]),
+    ]
+}
+
+/// Triangles of the boost bar and of the indicators of the nearest target and
+/// of the incoming rockets that are not on screen
+pub fn shapes(
+    world: &World,
+    player: usize,
+    position: &::na::Isometry3<f32>,
+    view_perspective: &::na::Matrix4<f32>,
+    aspect: f32,
+) -> Vec<HudVertex> {
+    let physic_world = world.read_resource::<::resource::PhysicWorld>();
+    let bodies = world.read_storage::<::component::PhysicBody>();
+    let sensors = world.read_storage::<::component::PhysicSensor>();
+    let targets = world.read_storage::<::component::Target>();
+    let rocket_controls = world.read_storage::<::component::RocketControl>();
+    let flight_controls = world.read_storage::<::component::FlightControl>();
+
+    let mut vertices = vec![];
+
+    let power = world.read_resource::<::resource::PlayersEntities>()[player]
+        .and_then(|entity| flight_controls.get(entity))
+        .map_or(0.0, |flight_control| flight_control.power.max(0.0).min(1.0));
+    rectangle(&mut vertices, BOOST_ORIGIN, BOOST_SIZE, BOOST_BACKGROUND_COLOR);
+    rectangle(&mut vertices, BOOST_ORIGIN, [BOOST_SIZE[0] * power, BOOST_SIZE[1]], BOOST_COLOR);
+
+    let origin = position.translation.vector;
+    let nearest_target = (&targets, &sensors).join()
+        .map(|(_, sensor)| sensor.position(&physic_world).translation.vector)
+        .min_by(|a, b| (a - origin).norm().partial_cmp(&(b - origin).norm()).unwrap());
+    if let Some(target) = nearest_target {
+        indicator(&mut vertices, &target, view_perspective, aspect, TARGET_COLOR);
+    }
+
+    for (_, body) in (&rocket_controls, &bodies).join() {
+        let rocket = body.get(&physic_world).position().translation.vector;
+        if (rocket - origin).norm() < THREAT_DISTANCE {
+            indicator(&mut vertices, &rocket, view_perspective, aspect, THREAT_COLOR);
+        }
+    }
+
+    vertices
+}
+
+/// Rectangle from its bottom left corner
+pub fn rectangle(vertices: &mut Vec<HudVertex>, origin: [f32; 2], size: [f32; 2], color: [f32; 4]) {
+    let (x0, y0) = (origin[0], origin[1]);
+    let (x1, y1) = (origin[0] + size[0], origin[1] - size[1]);
+    for &position in &[[x0, y0], [x1, y0], [x1, y1], [x1, y1], [x0, y1], [x0, y0]] {
+        vertices.push(HudVertex { position, color });
+    }
+}
+
+/// Arrow on the border of the viewport pointing toward the position if it is off screen
+fn indicator(
+    vertices: &mut Vec<HudVertex>,
+    position: &::na::Vector3<f32>,
+    view_perspective: &::na::Matrix4<f32>,
+    aspect: f32,
+    color: [f32; 4],
+) {
+    let clip = view_perspective * ::na::Vector4::new(position[0], position[1], position[2], 1.0);
+    // Y is flipped like in the vertex shader
+    let (x, y) = (clip[0] / clip[3].abs(), -clip[1] / clip[3].abs());
+    if clip[3] > 0.0 && x.abs() <= 1.0 && y.abs() <= 1.0 {
+        return;
+    }
+
+    // Behind the camera right in the center: point downward
+    let (x, y) = if x.abs() < ::std::f32::EPSILON && y.abs() < ::std::f32::EPSILON {
+        (0.0, 1.0)
+    } else {
+        (x, y)
+    };
+
+    let scale = INDICATOR_MARGIN / x.abs().max(y.abs());
+    let center = [x * scale, y * scale];
+
+    // Arrow is built where x and y have the same unit so it is not stretched
+    let direction = ::na::Vector2::new(x * aspect, y).normalize();
+    let normal = ::na::Vector2::new(-direction[1], direction[0]);
+    let tip = direction * INDICATOR_SIZE;
+    let left = normal * INDICATOR_SIZE * 0.6 - direction * INDICATOR_SIZE * 0.5;
+    let right = -normal * INDICATOR_SIZE * 0.6 - direction * INDICATOR_SIZE * 0.5;
+    for point in &[tip, left, right] {
+        vertices.push(HudVertex {
+            position: [center[0] + point[0] / aspect, center[1] + point[1]],
+            color,
+        });
+    }
+}
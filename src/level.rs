@@ -1,3 +1,4 @@
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct LevelBuilder {
     pub half_size: usize,
     pub x_shift: bool,
@@ -9,6 +10,75 @@ pub struct LevelBuilder {
     pub mine: usize,
     pub rocket_launcher: usize,
     pub target: usize,
+    #[serde(default)]
+    pub theme: ::texture::Theme,
+}
+
+impl Default for LevelBuilder {
+    fn default() -> Self {
+        Preset::Easy.level_builder()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, EnumIterator)]
+pub enum Preset {
+    Easy,
+    Hard,
+    Huge,
+}
+
+impl Preset {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Preset::Easy => "Easy",
+            Preset::Hard => "Hard",
+            Preset::Huge => "Huge",
+        }
+    }
+
+    pub fn level_builder(&self) -> LevelBuilder {
+        match *self {
+            Preset::Easy => LevelBuilder {
+                half_size: 9,
+                x_shift: false,
+                y_shift: false,
+                z_shift: false,
+                percent: 5.0,
+                unit: 1.0,
+                columns: 0,
+                rocket_launcher: 1,
+                mine: 1,
+                target: 1,
+                theme: ::texture::Theme::default(),
+            },
+            Preset::Hard => LevelBuilder {
+                half_size: 9,
+                x_shift: true,
+                y_shift: false,
+                z_shift: true,
+                percent: 10.0,
+                unit: 1.0,
+                columns: 2,
+                rocket_launcher: 3,
+                mine: 5,
+                target: 3,
+                theme: ::texture::Theme::default(),
+            },
+            Preset::Huge => LevelBuilder {
+                half_size: 15,
+                x_shift: true,
+                y_shift: true,
+                z_shift: true,
+                percent: 10.0,
+                unit: 1.0,
+                columns: 5,
+                rocket_launcher: 5,
+                mine: 10,
+                target: 5,
+                theme: ::texture::Theme::default(),
+            },
+        }
+    }
 }
 
 impl LevelBuilder {
@@ -37,47 +107,78 @@ impl LevelBuilder {
             maze
         };
 
-        let colors = maze.build_colors();
-        for (wall, color) in colors {
-            ::entity::create_wall(::util::to_world(&wall, self.unit), color, world);
-        }
-
         let mut tiles = ::tile::build_maze(&maze);
         for tile in &mut tiles {
             tile.position.translation.vector *= self.unit;
             tile.width *= self.unit;
             tile.height *= self.unit;
         }
-        world.add_resource(::resource::Tiles(tiles));
 
+        let walls = maze.clone();
         let mut tubes = ::tube::build_tubes(self.columns, &mut maze);
         for tube in &mut tubes {
             tube.position.translation.vector *= self.unit;
-            ::entity::create_tube(tube, world);
         }
-        world.add_resource(::resource::Tubes(tubes));
+
+        self.build_structure(&walls, tiles, tubes, world);
+
+        // Each entity takes its own free cell, stop placing them once the maze is full
+        let mut free_cells = maze.shuffled_free_cells();
 
         for _ in 0..self.mine {
-            let pos = maze.random_free();
-            maze.walls.insert(pos);
+            let pos = match free_cells.pop() {
+                Some(pos) => pos,
+                None => return,
+            };
             ::entity::create_mine(::util::to_world(&pos, self.unit), world);
         }
 
-        for _ in 0..self.target{
-            let pos = maze.random_free();
-            maze.walls.insert(pos);
+        for _ in 0..self.target {
+            let pos = match free_cells.pop() {
+                Some(pos) => pos,
+                None => return,
+            };
             ::entity::create_target(::util::to_world(&pos, self.unit), world);
         }
 
         for _ in 0..self.rocket_launcher {
-            let pos = maze.random_free();
-            maze.walls.insert(pos);
+            let pos = match free_cells.pop() {
+                Some(pos) => pos,
+                None => return,
+            };
             let isometry = ::na::Isometry3::new(
-                ::util::to_world(&pos, 1.0),
+                ::util::to_world(&pos, self.unit),
                 ::na::zero(),
             );
 
-            ::entity::create_rocket_launcher(isometry, world);
+            ::entity::create_rocket_launcher(isometry, 0.0, world);
         }
     }
+
+    /// Walls of the maze, tiles and tubes, the world must not contain any entity
+    pub fn build_structure(
+        &self,
+        maze: &::maze::Maze<::na::U3>,
+        tiles: Vec<::tile::Tile>,
+        tubes: Vec<::tube::Tube>,
+        world: &mut ::specs::World,
+    ) {
+        world.add_resource(::minimap::MazeMap::new(maze, self.unit));
+        world.add_resource(::minimap::Explored::default());
+        world.add_resource(::debug::Paths::default());
+
+        let colors = maze.build_colors();
+        for (wall, color) in colors {
+            ::entity::create_wall(::util::to_world(&wall, self.unit), color, world);
+        }
+
+        world.add_resource(::resource::Tiles(tiles));
+        world.add_resource(self.theme);
+        world.add_resource(::resource::Statistics::default());
+
+        for tube in &tubes {
+            ::entity::create_tube(tube, world);
+        }
+        world.add_resource(::resource::Tubes(tubes));
+    }
 }
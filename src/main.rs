@@ -19,6 +19,7 @@ extern crate ncollide;
 extern crate nphysics3d as nphysics;
 extern crate pathfinding;
 extern crate rand;
+extern crate rodio;
 extern crate ron;
 extern crate rusttype;
 extern crate serde;
@@ -54,46 +55,27 @@ mod level;
 mod entity;
 mod menu;
 mod world_action;
+mod display;
+mod skybox;
+mod audio;
+mod event;
+mod hud;
+mod minimap;
+mod debug;
+mod snapshot;
 
-pub use configuration::CFG;
-
-use show_message::OkOrShow;
+use show_message::{OkOrShow, SomeOrShow};
 use game_state::GameState;
 use vulkano_win::VkSurfaceBuild;
-use vulkano::instance::Instance;
+use vulkano::instance::{Instance, InstanceExtensions};
 use std::time::Duration;
 use std::time::Instant;
 use std::thread;
-use specs::{DispatcherBuilder, World};
+use specs::{Dispatcher, DispatcherBuilder, RunNow, World};
 use world_action::WorldAction;
+use debug::Timed;
 
-fn main() {
-    ::std::env::set_var("WINIT_UNIX_BACKEND", "x11");
-    let mut save = ::resource::Save::new();
-
-    let mut gilrs = gilrs::Gilrs::new()
-        .ok_or_show(|e| format!("Failed to initialize gilrs: {}\n\n{:#?}", e, e));
-
-    let instance = {
-        let extensions = vulkano_win::required_extensions();
-        let info = app_info_from_cargo_toml!();
-        Instance::new(Some(&info), &extensions, None).ok_or_show(|e| {
-            format!("Failed to create Vulkan instance.\nPlease see if you graphic cards support Vulkan and if so update your drivers\n\n{}", e)
-        })
-    };
-
-    let mut events_loop = winit::EventsLoop::new();
-    let window = winit::WindowBuilder::new()
-        .with_fullscreen(Some(events_loop.get_primary_monitor()))
-        .build_vk_surface(&events_loop, instance.clone())
-        .ok_or_show(|e| format!("Failed to build vulkan window: {}\n\n{:#?}", e, e));
-
-    try_multiple_time!(window.window().set_cursor_state(winit::CursorState::Grab))
-        .ok_or_show(|e| format!("Failed to grab cursor: {}", e));
-    window.window().set_cursor(winit::MouseCursor::NoneCursor);
-
-    let mut graphics = graphics::Graphics::new(&window, &mut save);
-
+fn create_world(configuration: ::configuration::Configuration) -> World {
     let mut world = World::new();
     world.register::<::component::PhysicBody>();
     world.register::<::component::PhysicSensor>();
@@ -107,46 +89,175 @@ fn main() {
     world.register::<::component::RocketControl>();
     world.register::<::component::MineControl>();
     world.register::<::component::ClosestPlayer>();
+    world.register::<::component::Trail>();
     world.add_resource(::resource::UpdateTime(0.0));
+    world.add_resource(::resource::Interpolation::default());
+    world.add_resource(::resource::TimeControl::default());
+    world.add_resource(::snapshot::Playing::default());
     world.add_resource(::resource::PhysicWorld::new());
+    world.add_resource(::resource::PhysicEntities::default());
     world.add_resource(::resource::PlayersEntities([None; 3]));
     world.add_resource(::resource::PlayersControllers([None, None, None]));
+    world.add_resource(::resource::DisconnectedGamepads([None; 3]));
     world.add_resource(::resource::Mode::Mode1Player);
     world.add_resource(::resource::Text::default());
+    world.add_resource(::resource::Notifications::default());
     world.add_resource(::resource::Font::new());
+    world.add_resource(::event::Events::default());
+    world.add_resource(::event::FrameEvents::default());
+    world.add_resource(::resource::Statistics::default());
+    world.add_resource(::minimap::MazeMap::default());
+    world.add_resource(::minimap::Explored::default());
+    world.add_resource(::debug::Overlay::default());
+    world.add_resource(::debug::SystemTimings::default());
+    world.add_resource(::debug::UpdateSteps::default());
+    world.add_resource(::debug::Paths::default());
+    world.add_resource(::configuration::ConfigurationWatcher::new());
+    world.add_resource(configuration);
     world.maintain();
+    world
+}
 
-    let mut update_dispatcher = DispatcherBuilder::new()
-        .with(::system::physic::PhysicSystem, "physic", &[])
-        .with(::system::target::TargetSystem, "target", &["physic"])
-        .with(::system::player_killer::PlayerKillerSystem, "player killer", &[])
-        .with(::system::rocket_launcher::RocketLauncherSystem, "rocket launcher", &[])
-        .with(::system::closest_player::ClosestPlayerSystem, "closest player", &[])
-        .with(::system::player_creator::PlayerCreatorSystem, "player creator", &[])
+fn create_update_dispatcher<'a, 'b>() -> Dispatcher<'a, 'b> {
+    DispatcherBuilder::new()
+        .with(Timed::new("physic", ::system::physic::PhysicSystem), "physic", &[])
+        .with(Timed::new("target", ::system::target::TargetSystem), "target", &["physic"])
+        .with(Timed::new("player killer", ::system::player_killer::PlayerKillerSystem), "player killer", &[])
+        .with(Timed::new("rocket launcher", ::system::rocket_launcher::RocketLauncherSystem), "rocket launcher", &[])
+        .with(Timed::new("closest player", ::system::closest_player::ClosestPlayerSystem), "closest player", &[])
+        .with(Timed::new("player creator", ::system::player_creator::PlayerCreatorSystem), "player creator", &[])
+        .with(Timed::new("statistics", ::system::statistics::StatisticsSystem), "statistics", &["player killer"])
+        .with(Timed::new("exploration", ::system::exploration::ExplorationSystem), "exploration", &["physic"])
         .with_barrier() // Draw barrier
-        .build();
+        .build()
+}
+
+/// Dispatch and maintain once then collect the events of the step into FrameEvents,
+/// return the durations of the dispatch and of the maintain
+fn update_step(world: &mut World, dispatcher: &mut Dispatcher) -> (f32, f32) {
+    world.write_resource::<::event::Events>().clear();
+
+    let stage_instant = Instant::now();
+    dispatcher.dispatch(&mut world.res);
+    let dispatch_time = ::debug::seconds(stage_instant.elapsed());
+
+    let stage_instant = Instant::now();
+    world.safe_maintain();
+    let maintain_time = ::debug::seconds(stage_instant.elapsed());
+
+    let events = world.read_resource::<::event::Events>();
+    world.write_resource::<::event::FrameEvents>().extend(events.iter().cloned());
+    (dispatch_time, maintain_time)
+}
+
+fn create_instance(extensions: &InstanceExtensions) -> ::std::sync::Arc<Instance> {
+    let info = app_info_from_cargo_toml!();
+    Instance::new(Some(&info), extensions, None).ok_or_show(|e| {
+        format!("Failed to create Vulkan instance.\nPlease see if you graphic cards support Vulkan and if so update your drivers\n\n{}", e)
+    })
+}
+
+/// Render the saved level from the first player spawn without any window
+fn headless_screenshot(path: &str, save: ::resource::Save, configuration: ::configuration::Configuration) {
+    let instance = create_instance(&InstanceExtensions::none());
+    let mut graphics = graphics::Graphics::new_headless(&instance, *save.vulkan_device_uuid(), &configuration);
+    let physical = graphics.device.physical_device();
+    if save.vulkan_device_uuid().map_or(false, |uuid| uuid != *physical.uuid()) {
+        eprintln!("WARNING: saved Vulkan device not available, falling back to {}", physical.name());
+    }
+    let mut audio = ::audio::Audio::null();
+
+    let mut world = create_world(configuration);
+    save.level_builder().build(&mut world);
+    world.add_resource(save);
+
+    // Spawn the player
+    update_step(&mut world, &mut create_update_dispatcher());
+    audio.update(&world);
+
+    graphics.render_offscreen(&world, [1280, 720])
+        .save(path)
+        .ok_or_show(|e| format!("Failed to write screenshot {}: {}", path, e));
+}
+
+fn main() {
+    ::std::env::set_var("WINIT_UNIX_BACKEND", "x11");
+    let mut save = ::resource::Save::new();
+    let configuration = ::configuration::Configuration::load().ok_or_show(|e| e);
+    let args = ::std::env::args().collect::<Vec<_>>();
+
+    if let Some(position) = args.iter().position(|arg| arg == "--screenshot") {
+        let path = args.get(position + 1)
+            .some_or_show("--screenshot expects the path of the PNG to write");
+        headless_screenshot(path, save, configuration);
+        return;
+    }
+
+    let mut gilrs = gilrs::Gilrs::new()
+        .ok_or_show(|e| format!("Failed to initialize gilrs: {}\n\n{:#?}", e, e));
+
+    let instance = create_instance(&vulkano_win::required_extensions());
+
+    let devices = ::graphics::DeviceInfo::list(&instance);
+    if args.iter().any(|arg| arg == "--list-devices") {
+        for (index, device) in devices.iter().enumerate() {
+            println!("{}: {}", index, device.description());
+        }
+        return;
+    }
+    if let Some(position) = args.iter().position(|arg| arg == "--device") {
+        let device = args.get(position + 1)
+            .and_then(|index| index.parse::<usize>().ok())
+            .and_then(|index| devices.get(index))
+            .some_or_show("--device expects the index of a device given by --list-devices");
+        save.set_vulkan_device_uuid_lazy(&device.uuid);
+    }
+
+    let mut events_loop = winit::EventsLoop::new();
+    let mut applied_display = (save.window_mode(), save.display().clone());
+    let window = ::display::window_builder(&events_loop, applied_display.0, &applied_display.1)
+        .build_vk_surface(&events_loop, instance.clone())
+        .ok_or_show(|e| format!("Failed to build vulkan window: {}\n\n{:#?}", e, e));
+
+    try_multiple_time!(window.window().set_cursor_state(winit::CursorState::Grab))
+        .ok_or_show(|e| format!("Failed to grab cursor: {}", e));
+    window.window().set_cursor(winit::MouseCursor::NoneCursor);
+
+    let mut graphics = graphics::Graphics::new(&window, &mut save, &configuration);
+
+    let mut world = create_world(configuration);
+    world.add_resource(::display::Monitors::new(&events_loop));
+    let devices = ::graphics::Devices::new(
+        devices,
+        graphics.device.physical_device().uuid().clone(),
+        save.vulkan_device_uuid().clone(),
+    );
+    if let Some(message) = devices.fallback_message() {
+        world.write_resource::<::resource::Notifications>().push(message);
+    }
+    world.add_resource(devices);
+
+    let mut audio = ::audio::Audio::new().unwrap_or_else(|error| {
+        world.write_resource::<::resource::Notifications>().push(error);
+        ::audio::Audio::null()
+    });
+
+    let mut update_dispatcher = create_update_dispatcher();
 
-    let frame_duration = Duration::new(0, (1_000_000_000.0 / ::CFG.fps as f32) as u32);
     let mut fps_counter = fps_counter::FPSCounter::new();
     let mut last_frame_instant = Instant::now();
     let mut last_update_instant = Instant::now();
+    // Time not simulated yet, always lower than a fixed step after an update
+    let mut accumulator = 0.0;
 
     let mut game_state = Box::new(game_state::GlobalMenu::new(&world)) as Box<GameState>;
 
-    ::level::LevelBuilder {
-        half_size: 9,
-        x_shift: false,
-        y_shift: false,
-        z_shift: false,
-        percent: 5.0,
-        unit: 1.0,
-        columns: 0,
-        rocket_launcher: 1,
-        mine: 1,
-        target: 1,
-    }.build(&mut world);
+    save.level_builder().build(&mut world);
+    world.add_resource(save);
 
     'main_loop: loop {
+        let mut take_screenshot = false;
+
         // Parse events
         let mut evs = vec![];
         events_loop.poll_events(|ev| {
@@ -177,12 +288,75 @@ fn main() {
                 } => {
                     break 'main_loop;
                 }
+                winit::Event::WindowEvent {
+                    event: winit::WindowEvent::KeyboardInput {
+                        input: winit::KeyboardInput {
+                            state: winit::ElementState::Pressed,
+                            virtual_keycode: Some(winit::VirtualKeyCode::F12),
+                            ..
+                        },
+                        ..
+                    },
+                    ..
+                } => {
+                    take_screenshot = true;
+                }
+                winit::Event::WindowEvent {
+                    event: winit::WindowEvent::KeyboardInput {
+                        input: winit::KeyboardInput {
+                            state: winit::ElementState::Pressed,
+                            virtual_keycode: Some(winit::VirtualKeyCode::F3),
+                            ..
+                        },
+                        ..
+                    },
+                    ..
+                } => {
+                    let mut overlay = world.write_resource::<::debug::Overlay>();
+                    overlay.enabled = !overlay.enabled;
+                }
+                winit::Event::WindowEvent {
+                    event: winit::WindowEvent::KeyboardInput {
+                        input: winit::KeyboardInput {
+                            state: winit::ElementState::Pressed,
+                            virtual_keycode: Some(winit::VirtualKeyCode::F4),
+                            ..
+                        },
+                        ..
+                    },
+                    ..
+                } => {
+                    let mut overlay = world.write_resource::<::debug::Overlay>();
+                    overlay.physic = !overlay.physic;
+                }
+                winit::Event::WindowEvent {
+                    event: winit::WindowEvent::KeyboardInput {
+                        input: winit::KeyboardInput {
+                            state: winit::ElementState::Pressed,
+                            virtual_keycode: Some(key),
+                            ..
+                        },
+                        ..
+                    },
+                    ..
+                } => {
+                    world.write_resource::<::resource::TimeControl>().key_pressed(key);
+                }
                 _ => (),
             }
             game_state = game_state.winit_event(ev, &mut world);
         }
         while let Some(ev) = gilrs.next_event() {
             gilrs.update(&ev);
+            match ev.event {
+                gilrs::EventType::Connected => {
+                    world.gamepad_connected(ev.id);
+                }
+                gilrs::EventType::Disconnected => {
+                    world.gamepad_disconnected(ev.id);
+                }
+                _ => (),
+            }
             game_state = game_state.gilrs_event(ev.id, ev.event, &mut world);
         }
         for (id, gamepad) in gilrs.gamepads() {
@@ -194,32 +368,146 @@ fn main() {
             break 'main_loop;
         }
 
-        // Update
-        let delta_time = last_update_instant.elapsed();
+        // Update with fixed steps, the remainder is kept for the next frame
+        let delta_time = ::debug::seconds(last_update_instant.elapsed());
         last_update_instant = Instant::now();
-        world.write_resource::<::resource::UpdateTime>().0 = delta_time
-            .as_secs()
-            .saturating_mul(1_000_000_000)
-            .saturating_add(delta_time.subsec_nanos() as u64)
-            as f32 / 1_000_000_000.0;
-
-        if game_state.paused(&world) {
-            world.write_resource::<::resource::UpdateTime>().0 = 0.0
+        world.write_resource::<::debug::Overlay>().push_frame_time(delta_time);
+        ::configuration::reload(&world);
+
+        let (step_time, max_steps) = {
+            let configuration = world.read_resource::<::configuration::Configuration>();
+            (configuration.update_step_time, configuration.max_update_steps)
+        };
+        let steps = {
+            let mut time_control = world.write_resource::<::resource::TimeControl>();
+            let mut update_time = world.write_resource::<::resource::UpdateTime>();
+            if game_state.paused(&world) || (time_control.frozen && time_control.single_steps == 0) {
+                // Systems still run once without time passing
+                accumulator = 0.0;
+                update_time.0 = 0.0;
+                1
+            } else if time_control.frozen {
+                time_control.single_steps -= 1;
+                accumulator = 0.0;
+                update_time.0 = step_time;
+                1
+            } else {
+                accumulator += delta_time * time_control.scale;
+                update_time.0 = step_time;
+                // Faster speeds need more steps per frame to be reached
+                let max_steps = (max_steps as f32 * time_control.scale.max(1.0)).ceil() as usize;
+                let steps = ((accumulator / step_time) as usize).min(max_steps);
+                accumulator -= steps as f32 * step_time;
+                // Drop the time that can't be caught up instead of slowing down further
+                accumulator = accumulator.min(step_time);
+                steps
+            }
+        };
+
+        world.write_resource::<::event::FrameEvents>().clear();
+        let mut dispatch_time = 0.0;
+        let mut maintain_time = 0.0;
+        for _ in 0..steps {
+            let (step_dispatch_time, step_maintain_time) = update_step(&mut world, &mut update_dispatcher);
+            dispatch_time += step_dispatch_time;
+            maintain_time += step_maintain_time;
         }
+        ::debug::collect_system_timings(&world);
+        world.write_resource::<::debug::UpdateSteps>().0 = steps;
+        world.write_resource::<::resource::Interpolation>().alpha = accumulator / step_time;
+        ::system::trail::TrailSystem.run_now(&world.res);
 
-        update_dispatcher.dispatch(&mut world.res);
+        let stage_instant = Instant::now();
+        audio.update(&world);
+        let audio_time = ::debug::seconds(stage_instant.elapsed());
 
-        world.safe_maintain();
+        // Apply display settings
+        {
+            let save = world.read_resource::<::resource::Save>();
+            if (save.window_mode(), save.display()) != (applied_display.0, &applied_display.1) {
+                applied_display = (save.window_mode(), save.display().clone());
+                ::display::apply(window.window(), &events_loop, applied_display.0, &applied_display.1);
+                graphics.set_present_mode(applied_display.1.present_mode, &window);
+            }
+        }
 
         // Draw
+        ::resource::update_notification(&world, delta_time);
+        ::debug::update_text(&world);
+        let stage_instant = Instant::now();
         game_state = graphics.draw(&mut world, &window, game_state);
+        let draw_time = ::debug::seconds(stage_instant.elapsed());
+        {
+            let mut overlay = world.write_resource::<::debug::Overlay>();
+            overlay.stages = vec![
+                ("dispatch", dispatch_time),
+                ("maintain", maintain_time),
+                ("audio", audio_time),
+                ("draw", draw_time),
+            ];
+        }
+
+        if take_screenshot {
+            let message = match graphics.screenshot(&world) {
+                Ok(path) => format!("Screenshot written to {}", path.display()),
+                Err(e) => e,
+            };
+            world.write_resource::<::resource::Notifications>().push(message);
+        }
 
         // Sleep
+        let fps = world.read_resource::<::configuration::Configuration>().fps;
+        let frame_duration = Duration::new(0, (1_000_000_000.0 / fps as f32) as u32);
         let elapsed = last_frame_instant.elapsed();
         if let Some(to_sleep) = frame_duration.checked_sub(elapsed) {
             thread::sleep(to_sleep);
         }
         last_frame_instant = Instant::now();
-        fps_counter.tick();
+        world.write_resource::<::debug::Overlay>().fps = fps_counter.tick();
+    }
+
+    ::snapshot::save_playing(&world);
+}
+
+#[cfg(test)]
+mod tests {
+    use specs::{Builder, DispatcherBuilder, World};
+
+    /// Kill the first player during the first step only
+    struct KillOnce(bool);
+
+    impl<'a> ::specs::System<'a> for KillOnce {
+        type SystemData = (
+            ::specs::ReadExpect<'a, ::resource::PlayersEntities>,
+            ::specs::WriteExpect<'a, ::event::Events>,
+        );
+
+        fn run(&mut self, (players_entities, mut events): Self::SystemData) {
+            if self.0 {
+                return;
+            }
+            self.0 = true;
+            events.push(::event::Event::PlayerKilled {
+                player: players_entities[0].unwrap(),
+                position: ::na::zero(),
+            });
+        }
+    }
+
+    #[test]
+    fn kill_is_counted_once() {
+        let mut world: World = ::create_world(::configuration::Configuration::built_in().unwrap());
+        let player = world.create_entity().build();
+        world.write_resource::<::resource::PlayersEntities>()[0] = Some(player);
+
+        let mut dispatcher = DispatcherBuilder::new()
+            .with(KillOnce(false), "kill once", &[])
+            .with(::system::statistics::StatisticsSystem, "statistics", &["kill once"])
+            .build();
+        ::update_step(&mut world, &mut dispatcher);
+        ::update_step(&mut world, &mut dispatcher);
+
+        assert_eq!(world.read_resource::<::resource::Statistics>().deaths, [1, 0, 0]);
+        assert_eq!(world.read_resource::<::event::FrameEvents>().len(), 1);
     }
 }
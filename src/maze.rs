@@ -5,6 +5,9 @@ use std::collections::HashMap;
 use std::hash::Hash;
 use std::ops::Mul;
 
+/// Cells around the maze that paths can go through
+const PATH_MARGIN: isize = 1;
+
 trait Pop {
     type Item;
     fn pop(&mut self) -> Option<Self::Item>;
@@ -612,6 +615,13 @@ where
         v
     }
 
+    /// Cell is inside the maze or in the margin around it
+    fn in_path_bounds(&self, cell: &::na::VectorN<isize, D>) -> bool {
+        cell.iter()
+            .zip(self.size.iter())
+            .all(|(&c, &s)| c >= -PATH_MARGIN && c < s + PATH_MARGIN)
+    }
+
     /// Only direct openings
     pub fn find_path_direct(
         &self,
@@ -628,7 +638,10 @@ where
                         .iter()
                         .all(|o| !self.walls.contains(&(o + cell.clone())))
                     {
-                        res.push((opening.cell.clone() + cell, opening.cost));
+                        let next = opening.cell.clone() + cell;
+                        if self.in_path_bounds(&next) {
+                            res.push((next, opening.cost));
+                        }
                     }
                 }
                 res
@@ -659,7 +672,10 @@ where
                         .iter()
                         .all(|o| !self.walls.contains(&(o + cell.clone())))
                     {
-                        res.push((opening.cell.clone() + cell, opening.cost));
+                        let next = opening.cell.clone() + cell;
+                        if self.in_path_bounds(&next) {
+                            res.push((next, opening.cost));
+                        }
                     }
                 }
                 res
@@ -675,20 +691,14 @@ where
         ).map(|p| p.0)
     }
 
-    pub fn random_free(&self) -> ::na::VectorN<isize, D> {
-        assert!(self.size.iter().all(|&s| s != 0));
-
-        let ranges: Vec<_> = self.size.iter().map(|&s| Range::new(0, s)).collect();
-        let mut rng = ::rand::thread_rng();
-
-        let mut vec =
-            ::na::VectorN::<isize, D>::from_iterator(ranges.iter().map(|r| r.sample(&mut rng)));
-        while self.walls.contains(&vec) {
-            vec = ::na::VectorN::<isize, D>::from_iterator(
-                ranges.iter().map(|r| r.sample(&mut rng)),
-            );
-        }
-        vec
+    /// Cells that are not walls in a random order
+    pub fn shuffled_free_cells(&self) -> Vec<::na::VectorN<isize, D>> {
+        let mut free = self.iterate_maze()
+            .into_iter()
+            .filter(|cell| !self.walls.contains(cell))
+            .collect::<Vec<_>>();
+        thread_rng().shuffle(&mut free);
+        free
     }
 
     pub fn free_in_square(
@@ -1,69 +1,308 @@
 pub struct Menu<A> {
-    buttons: Vec<Button<A>>,
+    entries: Vec<Entry<A>>,
     cursor: usize,
+    /// Entry of the submenu currently opened
+    opened: Option<usize>,
+    /// The text entry under the cursor is being edited
+    editing: bool,
 }
 
-impl<A: Copy> Menu<A> {
-    fn new(buttons: Vec<Button<A>>) -> Self {
-        assert!(buttons.len() != 0);
+enum Entry<A> {
+    Button {
+        name: String,
+        left: A,
+        right: A,
+    },
+    Slider {
+        name: String,
+        value: f32,
+        min: f32,
+        max: f32,
+        step: f32,
+        action: fn(f32) -> A,
+    },
+    Toggle {
+        name: String,
+        value: bool,
+        action: fn(bool) -> A,
+    },
+    Choice {
+        name: String,
+        choices: Vec<String>,
+        index: usize,
+        action: fn(usize) -> A,
+    },
+    TextEntry {
+        name: String,
+        value: String,
+        action: fn(String) -> A,
+    },
+    Submenu {
+        name: String,
+        menu: Menu<A>,
+    },
+    Back,
+}
+
+impl<A> Entry<A> {
+    fn label(&self, editing: bool) -> String {
+        match *self {
+            Entry::Button { ref name, .. } => name.clone(),
+            Entry::Slider { ref name, value, step, .. } => {
+                let decimals = (0..5)
+                    .find(|&d| {
+                        let shifted = step * 10_f32.powi(d);
+                        (shifted - shifted.round()).abs() < 1e-3
+                    })
+                    .unwrap_or(5) as usize;
+                format!("{}: {:.*}", name, decimals, value)
+            }
+            Entry::Toggle { ref name, value, .. } => {
+                format!("{}: {}", name, if value { "on" } else { "off" })
+            }
+            Entry::Choice { ref name, ref choices, index, .. } => {
+                format!("{}: {}", name, choices[index])
+            }
+            Entry::TextEntry { ref name, ref value, .. } => {
+                format!("{}: {}{}", name, value, if editing { "_" } else { "" })
+            }
+            Entry::Submenu { ref name, .. } => format!("{}...", name),
+            Entry::Back => "Back".to_string(),
+        }
+    }
+
+    fn set_name(&mut self, new_name: String) {
+        match *self {
+            Entry::Button { ref mut name, .. }
+            | Entry::Slider { ref mut name, .. }
+            | Entry::Toggle { ref mut name, .. }
+            | Entry::Choice { ref mut name, .. }
+            | Entry::TextEntry { ref mut name, .. }
+            | Entry::Submenu { ref mut name, .. } => *name = new_name,
+            Entry::Back => (),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Event {
+    Up,
+    Down,
+    Left,
+    Right,
+    Back,
+    Char(char),
+    Erase,
+    Validate,
+}
+
+enum Response<A> {
+    Action(A),
+    Back,
+    Nothing,
+}
+
+impl<A: Clone> Menu<A> {
+    fn new(entries: Vec<Entry<A>>) -> Self {
+        assert!(entries.len() != 0);
         Menu {
-            buttons,
+            entries,
             cursor: 0,
+            opened: None,
+            editing: false,
         }
     }
 
+    /// Number of entries of the deepest opened menu
     pub fn len(&self) -> usize {
-        self.buttons.len()
+        match self.opened_submenu() {
+            Some(menu) => menu.len(),
+            None => self.entries.len(),
+        }
     }
 
-    pub fn glyphs(&self, font: &::rusttype::Font<'static>) -> Vec<::rusttype::PositionedGlyph<'static>> {
-        let texts = self.buttons.iter()
-            .map(|b| b.name.clone())
+    pub fn glyphs(&self, font: &::rusttype::Font<'static>, text_scale: f32) -> Vec<::rusttype::PositionedGlyph<'static>> {
+        if let Some(menu) = self.opened_submenu() {
+            return menu.glyphs(font, text_scale);
+        }
+
+        let texts = self.entries.iter()
+            .enumerate()
+            .map(|(i, e)| e.label(self.editing && i == self.cursor))
             .collect::<Vec<_>>();
 
-        ::util::menu_layout(texts, Some(self.cursor), font)
+        ::util::menu_layout(texts, Some(self.cursor), font, text_scale)
     }
 
-    pub fn reset_name(&mut self, button: usize, name: String) {
-        self.buttons[button].name = name;
+    pub fn reset_name(&mut self, entry: usize, name: String) {
+        self.entries[entry].set_name(name);
     }
 
-    pub fn up(&mut self) {
-        self.cursor -= 1;
-        self.cursor %= self.buttons.len();
+    /// Replace the menu by a new one with the same layout keeping cursors and opened submenus
+    pub fn refresh(&mut self, mut menu: Menu<A>) {
+        if menu.entries.len() == self.entries.len() {
+            menu.cursor = self.cursor;
+            menu.opened = self.opened;
+            menu.editing = self.editing;
+            let editing = self.editing;
+            for (new, old) in menu.entries.iter_mut().zip(self.entries.drain(..)) {
+                match old {
+                    Entry::Submenu { menu: mut old_menu, .. } => {
+                        if let Entry::Submenu { menu: ref mut new_menu, .. } = *new {
+                            let new_submenu = ::std::mem::replace(new_menu, Menu::new(vec![Entry::Back]));
+                            old_menu.refresh(new_submenu);
+                            *new_menu = old_menu;
+                        }
+                    }
+                    // Don't lose the text being typed
+                    Entry::TextEntry { value: old_value, .. } => {
+                        if let Entry::TextEntry { value: ref mut new_value, .. } = *new {
+                            if editing {
+                                *new_value = old_value;
+                            }
+                        }
+                    }
+                    _ => (),
+                }
+            }
+        }
+        *self = menu;
     }
 
-    pub fn down(&mut self) {
-        self.cursor += 1;
-        self.cursor %= self.buttons.len();
+    fn opened_submenu(&self) -> Option<&Menu<A>> {
+        self.opened.map(|opened| match self.entries[opened] {
+            Entry::Submenu { ref menu, .. } => menu,
+            _ => unreachable!(),
+        })
     }
 
-    pub fn left(&mut self) -> A {
-        self.buttons[self.cursor].left
+    fn process(&mut self, event: Event) -> Response<A> {
+        if let Some(opened) = self.opened {
+            let response = match self.entries[opened] {
+                Entry::Submenu { ref mut menu, .. } => menu.process(event),
+                _ => unreachable!(),
+            };
+            return match response {
+                Response::Back => {
+                    self.opened = None;
+                    Response::Nothing
+                }
+                response @ _ => response,
+            };
+        }
+
+        if self.editing {
+            let editing = &mut self.editing;
+            return match self.entries[self.cursor] {
+                Entry::TextEntry { ref mut value, action, .. } => match event {
+                    Event::Char(c) => {
+                        if !c.is_control() {
+                            value.push(c);
+                        }
+                        Response::Nothing
+                    }
+                    Event::Erase => {
+                        value.pop();
+                        Response::Nothing
+                    }
+                    Event::Validate => {
+                        *editing = false;
+                        Response::Action(action(value.clone()))
+                    }
+                    Event::Back => {
+                        *editing = false;
+                        Response::Nothing
+                    }
+                    _ => Response::Nothing,
+                },
+                _ => unreachable!(),
+            };
+        }
+
+        let len = self.entries.len();
+        match event {
+            Event::Up => {
+                self.cursor = (self.cursor + len - 1) % len;
+                Response::Nothing
+            }
+            Event::Down => {
+                self.cursor = (self.cursor + 1) % len;
+                Response::Nothing
+            }
+            Event::Back => Response::Back,
+            Event::Char(_) | Event::Erase => Response::Nothing,
+            Event::Left => self.activate(false),
+            Event::Right | Event::Validate => self.activate(true),
+        }
     }
 
-    pub fn right(&mut self) -> A {
-        self.buttons[self.cursor].right
+    fn activate(&mut self, right: bool) -> Response<A> {
+        let cursor = self.cursor;
+        match self.entries[cursor] {
+            Entry::Button { ref left, right: ref right_action, .. } => {
+                Response::Action(if right { right_action.clone() } else { left.clone() })
+            }
+            Entry::Slider { ref mut value, min, max, step, action, .. } => {
+                let new_value = if right { *value + step } else { *value - step };
+                let new_value = min + ((new_value - min) / step).round() * step;
+                let new_value = new_value.max(min).min(max);
+                if new_value != *value {
+                    *value = new_value;
+                    Response::Action(action(new_value))
+                } else {
+                    Response::Nothing
+                }
+            }
+            Entry::Toggle { ref mut value, action, .. } => {
+                *value = !*value;
+                Response::Action(action(*value))
+            }
+            Entry::Choice { ref choices, ref mut index, action, .. } => {
+                *index = if right {
+                    (*index + 1) % choices.len()
+                } else {
+                    (*index + choices.len() - 1) % choices.len()
+                };
+                Response::Action(action(*index))
+            }
+            Entry::TextEntry { .. } => {
+                self.editing = true;
+                Response::Nothing
+            }
+            Entry::Submenu { .. } => {
+                self.opened = Some(cursor);
+                Response::Nothing
+            }
+            Entry::Back => Response::Back,
+        }
+    }
+
+    fn event(&mut self, event: Event) -> Option<A> {
+        match self.process(event) {
+            Response::Action(action) => Some(action),
+            Response::Back | Response::Nothing => None,
+        }
     }
 
     pub fn control_event(&mut self, event: ::resource::Control) -> Option<A> {
         match event {
-            ::resource::Control::Up => {
-                self.up();
-                None
-            },
-            ::resource::Control::Down => {
-                self.down();
-                None
-            }
-            ::resource::Control::Left => Some(self.left()),
-            ::resource::Control::Right => Some(self.right()),
+            ::resource::Control::Up => self.event(Event::Up),
+            ::resource::Control::Down => self.event(Event::Down),
+            ::resource::Control::Left => self.event(Event::Left),
+            ::resource::Control::Right => self.event(Event::Right),
+            ::resource::Control::Menu => self.event(Event::Back),
             _ => None,
         }
     }
 
     pub fn winit_event(&mut self, event: ::winit::Event, player: Option<usize>, controllers: &::resource::PlayersControllers) -> Option<A> {
         match event {
+            ::winit::Event::WindowEvent {
+                event: ::winit::WindowEvent::ReceivedCharacter(c),
+                ..
+            } => {
+                self.event(Event::Char(c))
+            }
             ::winit::Event::WindowEvent {
                 event:
                     ::winit::WindowEvent::KeyboardInput {
@@ -77,11 +316,15 @@ impl<A: Copy> Menu<A> {
                     },
                 ..
             } => {
-                let control = match virtual_keycode {
-                    ::winit::VirtualKeyCode::Up => Some(::resource::Control::Up),
-                    ::winit::VirtualKeyCode::Down => Some(::resource::Control::Down),
-                    ::winit::VirtualKeyCode::Left => Some(::resource::Control::Left),
-                    ::winit::VirtualKeyCode::Right => Some(::resource::Control::Right),
+                let event = match virtual_keycode {
+                    ::winit::VirtualKeyCode::Up => Some(Event::Up),
+                    ::winit::VirtualKeyCode::Down => Some(Event::Down),
+                    ::winit::VirtualKeyCode::Left => Some(Event::Left),
+                    ::winit::VirtualKeyCode::Right => Some(Event::Right),
+                    ::winit::VirtualKeyCode::Escape => Some(Event::Back),
+                    ::winit::VirtualKeyCode::Back => Some(Event::Erase),
+                    ::winit::VirtualKeyCode::Return => Some(Event::Validate),
+                    _ if self.is_editing() => None,
                     _ => {
                         controllers.iter()
                             .enumerate()
@@ -97,12 +340,19 @@ impl<A: Copy> Menu<A> {
                             })
                             .flat_map(|c| c.mapping.iter())
                             .find(|c| c.0 == virtual_keycode)
-                            .map(|c| c.1)
+                            .and_then(|c| match c.1 {
+                                ::resource::Control::Up => Some(Event::Up),
+                                ::resource::Control::Down => Some(Event::Down),
+                                ::resource::Control::Left => Some(Event::Left),
+                                ::resource::Control::Right => Some(Event::Right),
+                                ::resource::Control::Menu => Some(Event::Back),
+                                _ => None,
+                            })
                     }
                 };
 
-                if let Some(control) = control {
-                    self.control_event(control)
+                if let Some(event) = event {
+                    self.event(event)
                 } else {
                     None
                 }
@@ -116,9 +366,9 @@ impl<A: Copy> Menu<A> {
             ::gilrs::EventType::AxisChanged(::gilrs::Axis::LeftStickX, value, _)
             | ::gilrs::EventType::AxisChanged(::gilrs::Axis::RightStickX, value, _) => {
                 if value == 1.0 {
-                    Some(self.right())
+                    self.event(Event::Right)
                 } else if value == -1.0 {
-                    Some(self.left())
+                    self.event(Event::Left)
                 } else {
                     None
                 }
@@ -126,54 +376,54 @@ impl<A: Copy> Menu<A> {
             ::gilrs::EventType::AxisChanged(::gilrs::Axis::LeftStickY, value, _)
             | ::gilrs::EventType::AxisChanged(::gilrs::Axis::RightStickY, value, _) => {
                 if value == 1.0 {
-                    self.up();
+                    self.event(Event::Up)
                 } else if value == -1.0 {
-                    self.down();
+                    self.event(Event::Down)
+                } else {
+                    None
                 }
-                None
             }
             ::gilrs::EventType::ButtonPressed(::gilrs::Button::South, _)
             | ::gilrs::EventType::ButtonPressed(::gilrs::Button::DPadDown, _) => {
-                self.down();
-                None
+                self.event(Event::Down)
             }
             ::gilrs::EventType::ButtonPressed(::gilrs::Button::North, _)
             | ::gilrs::EventType::ButtonPressed(::gilrs::Button::DPadUp, _) => {
-                self.up();
-                None
+                self.event(Event::Up)
             }
             ::gilrs::EventType::ButtonPressed(::gilrs::Button::East, _)
             | ::gilrs::EventType::ButtonPressed(::gilrs::Button::DPadRight, _) => {
-                Some(self.right())
+                self.event(Event::Right)
             }
             ::gilrs::EventType::ButtonPressed(::gilrs::Button::West, _)
             | ::gilrs::EventType::ButtonPressed(::gilrs::Button::DPadLeft, _) => {
-                Some(self.left())
+                self.event(Event::Left)
             }
             _ => None,
         }
     }
-}
 
-struct Button<A> {
-    name: String,
-    left: A,
-    right: A,
+    fn is_editing(&self) -> bool {
+        match self.opened_submenu() {
+            Some(menu) => menu.is_editing(),
+            None => self.editing,
+        }
+    }
 }
 
 pub struct MenuBuilder<A> {
-    buttons: Vec<Button<A>>,
+    entries: Vec<Entry<A>>,
 }
 
-impl<A: Copy> MenuBuilder<A> {
+impl<A: Clone> MenuBuilder<A> {
     pub fn new() -> Self {
         MenuBuilder {
-            buttons: vec![],
+            entries: vec![],
         }
     }
 
     pub fn add_left_right(mut self, name: String, left: A, right: A) -> Self {
-        self.buttons.push(Button {
+        self.entries.push(Entry::Button {
             name,
             left,
             right,
@@ -182,15 +432,70 @@ impl<A: Copy> MenuBuilder<A> {
     }
 
     pub fn add_middle(mut self, name: String, middle: A) -> Self {
-        self.buttons.push(Button {
+        self.entries.push(Entry::Button {
             name,
-            left: middle,
+            left: middle.clone(),
             right: middle,
         });
         self
     }
 
+    /// Value is rounded to min plus a multiple of step and clamped in [min, max]
+    pub fn add_slider(mut self, name: String, value: f32, min: f32, max: f32, step: f32, action: fn(f32) -> A) -> Self {
+        assert!(min <= max && step > 0.0);
+        self.entries.push(Entry::Slider {
+            name,
+            value: value.max(min).min(max),
+            min,
+            max,
+            step,
+            action,
+        });
+        self
+    }
+
+    pub fn add_toggle(mut self, name: String, value: bool, action: fn(bool) -> A) -> Self {
+        self.entries.push(Entry::Toggle {
+            name,
+            value,
+            action,
+        });
+        self
+    }
+
+    pub fn add_choice(mut self, name: String, choices: Vec<String>, index: usize, action: fn(usize) -> A) -> Self {
+        assert!(index < choices.len());
+        self.entries.push(Entry::Choice {
+            name,
+            choices,
+            index,
+            action,
+        });
+        self
+    }
+
+    /// Text is edited with the keyboard only: [→] to start, [RETURN] to validate, [ESCAPE] to cancel
+    pub fn add_text_entry(mut self, name: String, value: String, action: fn(String) -> A) -> Self {
+        self.entries.push(Entry::TextEntry {
+            name,
+            value,
+            action,
+        });
+        self
+    }
+
+    /// A "Back" entry is appended to the submenu
+    pub fn add_submenu(mut self, name: String, submenu: MenuBuilder<A>) -> Self {
+        let mut entries = submenu.entries;
+        entries.push(Entry::Back);
+        self.entries.push(Entry::Submenu {
+            name,
+            menu: Menu::new(entries),
+        });
+        self
+    }
+
     pub fn build(self) -> Menu<A> {
-        Menu::new(self.buttons)
+        Menu::new(self.entries)
     }
 }
@@ -0,0 +1,220 @@
+use specs::{Join, World};
+use std::collections::HashSet;
+
+/// Cells around a visited cell that are revealed
+const REVEAL_RADIUS: isize = 2;
+/// Half size of the markers relative to the unit of the maze
+const MARKER_SIZE: f32 = 0.4;
+/// Space between the minimap and the border of the viewport in pixels
+pub const MARGIN: f32 = 10.0;
+const WALL_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.35];
+const TARGET_COLOR: [f32; 4] = [0.35, 0.75, 0.4, 1.0];
+const MINE_COLOR: [f32; 4] = [0.9, 0.35, 0.3, 1.0];
+const ROCKET_LAUNCHER_COLOR: [f32; 4] = [0.6, 0.2, 0.6, 1.0];
+
+/// Faces of walls of the maze that look onto a free cell
+pub struct MazeMap {
+    /// Free cell and corners of the face in world coordinates
+    faces: Vec<(::na::Vector3<isize>, [::na::Vector3<f32>; 4])>,
+    /// Used to find paths in physic debug mode
+    pub maze: ::maze::Maze<::na::U3>,
+    center: ::na::Vector3<f32>,
+    radius: f32,
+    unit: f32,
+}
+
+impl Default for MazeMap {
+    fn default() -> Self {
+        MazeMap {
+            faces: vec![],
+            maze: ::maze::Maze::new_empty(),
+            center: ::na::zero(),
+            radius: 1.0,
+            unit: 1.0,
+        }
+    }
+}
+
+impl MazeMap {
+    pub fn new(maze: &::maze::Maze<::na::U3>, unit: f32) -> Self {
+        let size = maze.size();
+        let inside = |cell: &::na::Vector3<isize>| (0..3).all(|i| cell[i] >= 0 && cell[i] < size[i]);
+
+        let mut faces = vec![];
+        for wall in &maze.walls {
+            for i in 0..3 {
+                for &sign in &[-1, 1] {
+                    let mut free = *wall;
+                    free[i] += sign;
+                    if !inside(&free) || maze.walls.contains(&free) {
+                        continue;
+                    }
+
+                    // Corners of the face of the cube of the wall toward the free cell
+                    let (j, k) = ((i + 1) % 3, (i + 2) % 3);
+                    let center = ::util::to_world(wall, unit);
+                    let mut corners = [center; 4];
+                    for (corner, &(a, b)) in corners.iter_mut().zip(&[(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]) {
+                        corner[i] += sign as f32 * unit / 2.0;
+                        corner[j] += a * unit / 2.0;
+                        corner[k] += b * unit / 2.0;
+                    }
+                    faces.push((free, corners));
+                }
+            }
+        }
+
+        let size = ::na::Vector3::new(size[0] as f32, size[1] as f32, size[2] as f32) * unit;
+        MazeMap {
+            faces,
+            maze: maze.clone(),
+            center: size / 2.0,
+            radius: size.norm() / 2.0,
+            unit,
+        }
+    }
+
+    pub fn cell(&self, position: &::na::Vector3<f32>) -> ::na::Vector3<isize> {
+        ::na::Vector3::new(
+            (position[0] / self.unit).floor() as isize,
+            (position[1] / self.unit).floor() as isize,
+            (position[2] / self.unit).floor() as isize,
+        )
+    }
+
+    /// Center of the cell in world coordinates
+    pub fn position(&self, cell: &::na::Vector3<isize>) -> ::na::Vector3<f32> {
+        ::util::to_world(cell, self.unit)
+    }
+
+    /// Perspective of the whole maze seen from behind the orientation
+    pub fn view_perspective(&self, orientation: &::na::UnitQuaternion<f32>) -> ::na::Matrix4<f32> {
+        let fov = ::std::f32::consts::FRAC_PI_4;
+        let distance = self.radius / (fov / 2.0).sin();
+        let view: ::na::Transform3<f32> = ::na::Similarity3::look_at_rh(
+            &::na::Point3::from_coordinates(self.center - orientation * ::na::Vector3::x() * distance),
+            &::na::Point3::from_coordinates(self.center),
+            &(orientation * ::na::Vector3::z()),
+            1.0,
+        ).to_superset();
+        let perspective = ::na::Perspective3::new(
+            1.0,
+            fov,
+            (distance - self.radius).max(0.01),
+            distance + self.radius,
+        );
+        perspective.unwrap() * view.unwrap()
+    }
+}
+
+/// Cells revealed to each player
+#[derive(Default)]
+pub struct Explored {
+    pub cells: [HashSet<::na::Vector3<isize>>; 3],
+    pub last: [Option<::na::Vector3<isize>>; 3],
+}
+
+impl Explored {
+    /// Reveal the cells around the cell
+    pub fn visit(&mut self, player: usize, cell: ::na::Vector3<isize>) {
+        if self.last[player] == Some(cell) {
+            return;
+        }
+        self.last[player] = Some(cell);
+        for x in -REVEAL_RADIUS..REVEAL_RADIUS + 1 {
+            for y in -REVEAL_RADIUS..REVEAL_RADIUS + 1 {
+                for z in -REVEAL_RADIUS..REVEAL_RADIUS + 1 {
+                    self.cells[player].insert(cell + ::na::Vector3::new(x, y, z));
+                }
+            }
+        }
+    }
+}
+
+/// Edges of the faces of walls known by the player as a line list
+pub fn walls(world: &World, player: usize, fog_of_war: bool) -> (Vec<::graphics::LineVertex>, [f32; 4]) {
+    let maze_map = world.read_resource::<MazeMap>();
+    let explored = world.read_resource::<Explored>();
+
+    let mut vertices = vec![];
+    for &(ref free, ref corners) in &maze_map.faces {
+        if fog_of_war && !explored.cells[player].contains(free) {
+            continue;
+        }
+        for i in 0..4 {
+            vertices.push(::graphics::LineVertex::new(&corners[i]));
+            vertices.push(::graphics::LineVertex::new(&corners[(i + 1) % 4]));
+        }
+    }
+    (vertices, WALL_COLOR)
+}
+
+/// Crosses on players, targets, mines and rocket launchers as line lists with their colors
+pub fn markers(
+    world: &World,
+    player: usize,
+    fog_of_war: bool,
+    player_colors: &[[f32; 4]; 3],
+) -> Vec<(Vec<::graphics::LineVertex>, [f32; 4])> {
+    let maze_map = world.read_resource::<MazeMap>();
+    let explored = world.read_resource::<Explored>();
+    let physic_world = world.read_resource::<::resource::PhysicWorld>();
+    let players_entities = world.read_resource::<::resource::PlayersEntities>();
+    let bodies = world.read_storage::<::component::PhysicBody>();
+    let sensors = world.read_storage::<::component::PhysicSensor>();
+    let targets = world.read_storage::<::component::Target>();
+    let mine_controls = world.read_storage::<::component::MineControl>();
+    let rocket_launchers = world.read_storage::<::component::RocketLauncher>();
+
+    let size = maze_map.unit * MARKER_SIZE;
+    let known = |position: &::na::Vector3<f32>| {
+        !fog_of_war || explored.cells[player].contains(&maze_map.cell(position))
+    };
+    let crosses = |positions: Vec<::na::Vector3<f32>>| {
+        positions.iter()
+            .filter(|position| known(*position))
+            .flat_map(|position| cross(position, size))
+            .collect::<Vec<_>>()
+    };
+
+    let mut markers = players_entities.iter()
+        .zip(player_colors.iter())
+        .filter_map(|(entity, color)| {
+            entity.and_then(|entity| bodies.get(entity))
+                .map(|body| (cross(&body.get(&physic_world).position().translation.vector, size), *color))
+        })
+        .collect::<Vec<_>>();
+
+    markers.push((
+        crosses((&targets, &sensors).join()
+            .map(|(_, sensor)| sensor.position(&physic_world).translation.vector)
+            .collect()),
+        TARGET_COLOR,
+    ));
+    markers.push((
+        crosses((&mine_controls, &bodies).join()
+            .map(|(_, body)| body.get(&physic_world).position().translation.vector)
+            .collect()),
+        MINE_COLOR,
+    ));
+    markers.push((
+        crosses(rocket_launchers.join()
+            .map(|rocket_launcher| rocket_launcher.position.translation.vector)
+            .collect()),
+        ROCKET_LAUNCHER_COLOR,
+    ));
+
+    markers.retain(|&(ref vertices, _)| !vertices.is_empty());
+    markers
+}
+
+pub fn cross(position: &::na::Vector3<f32>, size: f32) -> Vec<::graphics::LineVertex> {
+    let mut vertices = vec![];
+    for i in 0..3 {
+        let mut delta = ::na::Vector3::new(0.0, 0.0, 0.0);
+        delta[i] = size;
+        vertices.push(::graphics::LineVertex::new(&(position - delta)));
+        vertices.push(::graphics::LineVertex::new(&(position + delta)));
+    }
+    vertices
+}
@@ -4,89 +4,269 @@ use graphics::Vertex;
 
 const THICKNESS: f32 = 0.05;
 
-const TILE: [Vertex; 36] = [
+/// Corners of the faces in the order of FACES, scaled to the tile by generate_tile
+const TILE: [[f32; 3]; 36] = [
     // Bottom
-    Vertex { position: [1.0, -1.0, -0.05], tex_coords: [0.5, 0.0] },
-    Vertex { position: [-1.0, -1.0, -0.05], tex_coords: [0.0, 0.0] },
-    Vertex { position: [-1.0, 1.0, -0.05], tex_coords: [0.0, 0.5] },
+    [1.0, -1.0, -0.05],
+    [-1.0, -1.0, -0.05],
+    [-1.0, 1.0, -0.05],
 
-    Vertex { position: [1.0, 1.0, -0.05], tex_coords: [0.5, 0.5] },
-    Vertex { position: [1.0, -1.0, -0.05], tex_coords: [0.5, 0.0] },
-    Vertex { position: [-1.0, 1.0, -0.05], tex_coords: [0.0, 0.5] },
+    [1.0, 1.0, -0.05],
+    [1.0, -1.0, -0.05],
+    [-1.0, 1.0, -0.05],
 
     // Top
-    Vertex { position: [-1.0, -1.0, 0.05], tex_coords: [0.5, 0.5] },
-    Vertex { position: [1.0, -1.0, 0.05], tex_coords: [1.0, 0.5] },
-    Vertex { position: [-1.0, 1.0, 0.05], tex_coords: [0.5, 1.0] },
-
-    Vertex { position: [1.0, -1.0, 0.05], tex_coords: [1.0, 0.5] },
-    Vertex { position: [1.0, 1.0, 0.05], tex_coords: [1.0, 1.0] },
-    Vertex { position: [-1.0, 1.0, 0.05], tex_coords: [0.5, 1.0] },
-
-    // Sides
-    // x=[0.5, 0.6], y=[0.0, 0.5]
-    Vertex { position: [-1.0, -1.0, -0.05], tex_coords: [0.5, 0.0] },
-    Vertex { position: [-1.0, -1.0, 0.05], tex_coords: [0.6, 0.0] },
-    Vertex { position: [-1.0, 1.0, -0.05], tex_coords: [0.5, 0.5] },
-
-    Vertex { position: [-1.0, -1.0, 0.05], tex_coords: [0.6, 0.0] },
-    Vertex { position: [-1.0, 1.0, 0.05], tex_coords: [0.6, 0.5] },
-    Vertex { position: [-1.0, 1.0, -0.05], tex_coords: [0.5, 0.5] },
-
-    // x=[0.6, 0.7], y=[0.0, 0.5]
-    Vertex { position: [1.0, -1.0, 0.05], tex_coords: [0.7, 0.0] },
-    Vertex { position: [1.0, -1.0, -0.05], tex_coords: [0.6, 0.0] },
-    Vertex { position: [1.0, 1.0, -0.05], tex_coords: [0.6, 0.5] },
-
-    Vertex { position: [1.0, 1.0, 0.05], tex_coords: [0.7, 0.5] },
-    Vertex { position: [1.0, -1.0, 0.05], tex_coords: [0.7, 0.0] },
-    Vertex { position: [1.0, 1.0, -0.05], tex_coords: [0.6, 0.5] },
-
-    // x=[0.5, 1.0], y=[0.5, 0.6]
-    Vertex { position: [-1.0, -1.0, -0.05], tex_coords: [0.5, 0.5] },
-    Vertex { position: [1.0, -1.0, -0.05], tex_coords: [1.0, 0.5] },
-    Vertex { position: [-1.0, -1.0, 0.05], tex_coords: [0.5, 0.6] },
-
-    Vertex { position: [1.0, -1.0, 0.05], tex_coords: [1.0, 0.6] },
-    Vertex { position: [-1.0, -1.0, 0.05], tex_coords: [0.5, 0.6] },
-    Vertex { position: [1.0, -1.0, -0.05], tex_coords: [1.0, 0.5] },
-
-    // x=[0.5, 1.0], y=[0.6, 0.7]
-    Vertex { position: [1.0, 1.0, -0.05], tex_coords: [1.0, 0.6] },
-    Vertex { position: [-1.0, 1.0, -0.05], tex_coords: [0.5, 0.6] },
-    Vertex { position: [-1.0, 1.0, 0.05], tex_coords: [0.5, 0.7] },
-
-    Vertex { position: [-1.0, 1.0, 0.05], tex_coords: [0.5, 0.7] },
-    Vertex { position: [1.0, 1.0, 0.05], tex_coords: [1.0, 0.7] },
-    Vertex { position: [1.0, 1.0, -0.05], tex_coords: [1.0, 0.6] },
+    [-1.0, -1.0, 0.05],
+    [1.0, -1.0, 0.05],
+    [-1.0, 1.0, 0.05],
+
+    [1.0, -1.0, 0.05],
+    [1.0, 1.0, 0.05],
+    [-1.0, 1.0, 0.05],
+
+    // Sides normal to x
+    [-1.0, -1.0, -0.05],
+    [-1.0, -1.0, 0.05],
+    [-1.0, 1.0, -0.05],
+
+    [-1.0, -1.0, 0.05],
+    [-1.0, 1.0, 0.05],
+    [-1.0, 1.0, -0.05],
+
+    [1.0, -1.0, 0.05],
+    [1.0, -1.0, -0.05],
+    [1.0, 1.0, -0.05],
+
+    [1.0, 1.0, 0.05],
+    [1.0, -1.0, 0.05],
+    [1.0, 1.0, -0.05],
+
+    // Sides normal to y
+    [-1.0, -1.0, -0.05],
+    [1.0, -1.0, -0.05],
+    [-1.0, -1.0, 0.05],
+
+    [1.0, -1.0, 0.05],
+    [-1.0, -1.0, 0.05],
+    [1.0, -1.0, -0.05],
+
+    [1.0, 1.0, -0.05],
+    [-1.0, 1.0, -0.05],
+    [-1.0, 1.0, 0.05],
+
+    [-1.0, 1.0, 0.05],
+    [1.0, 1.0, 0.05],
+    [1.0, 1.0, -0.05],
 ];
 
-pub fn generate_tile(width: isize, height: isize) -> Vec<Vertex> {
+pub fn generate_tile(net: &TileNet) -> Vec<Vertex> {
+    let half_extents = net.half_extents;
     TILE.iter()
-        .cloned()
-        .map(|mut vertex| {
-            if vertex.tex_coords[0] == 0.6 {
-                vertex.tex_coords[0] = 0.5 + THICKNESS / width as f32;
-            }
-            if vertex.tex_coords[0] == 0.7 {
-                vertex.tex_coords[0] = 0.5 + THICKNESS * 2.0 / width as f32;
-            }
-            if vertex.tex_coords[1] == 0.6 {
-                vertex.tex_coords[1] = 0.5 + THICKNESS / height as f32;
-            }
-            if vertex.tex_coords[1] == 0.7 {
-                vertex.tex_coords[1] = 0.5 + THICKNESS * 2.0 / height as f32;
-            }
+        .enumerate()
+        .map(|(i, corner)| {
             // IDEA: unaligned tiles can be cool
-            vertex.position[0] *= width as f32 / 2.0;
-            vertex.position[1] *= height as f32 / 2.0;
+            let position = [
+                corner[0].signum() * half_extents[0],
+                corner[1].signum() * half_extents[1],
+                corner[2].signum() * half_extents[2],
+            ];
+            Vertex::new(position, net.tex_coords(FACES[i / 6], position))
+        })
+        .collect::<Vec<_>>()
+}
 
-            vertex.position[0] = vertex.position[0] - vertex.position[0].signum() * 0.05;
-            vertex.position[1] = vertex.position[1] - vertex.position[1].signum() * 0.05;
+/// Half extents of the cuboid generated by generate_tile
+pub fn tile_half_extents(width: isize, height: isize) -> [f32; 3] {
+    [
+        width as f32 / 2.0 - 0.05,
+        height as f32 / 2.0 - 0.05,
+        THICKNESS / 2.0,
+    ]
+}
 
-            vertex.position[2] = vertex.position[2].signum() * THICKNESS / 2.0;
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Face {
+    Bottom,
+    Top,
+    Left,
+    Right,
+    Front,
+    Back,
+}
 
-            vertex
-        })
-        .collect::<Vec<_>>()
+const FACES: [Face; 6] = [Face::Bottom, Face::Top, Face::Left, Face::Right, Face::Front, Face::Back];
+
+impl Face {
+    /// Axes of the face coordinates, axis of the normal and its sign
+    fn axes(&self) -> (usize, usize, usize, f32) {
+        match *self {
+            Face::Bottom => (0, 1, 2, -1.0),
+            Face::Top => (0, 1, 2, 1.0),
+            Face::Left => (1, 2, 0, -1.0),
+            Face::Right => (1, 2, 0, 1.0),
+            Face::Front => (0, 2, 1, -1.0),
+            Face::Back => (0, 2, 1, 1.0),
+        }
+    }
+}
+
+/// Pixels around each face in the texture so that filtering does not mix faces
+const GUTTER: u32 = 2;
+
+/// Unwrapped cuboid of a tile: where each face lies in its texture
+pub struct TileNet {
+    pub dimensions: [u32; 2],
+    pub half_extents: [f32; 3],
+    pub pixels_per_unit: u32,
+    /// Origin of the gutter and size in pixels of each face in the order of FACES
+    regions: [([u32; 2], [u32; 2]); 6],
+}
+
+impl TileNet {
+    pub fn new(width: isize, height: isize, pixels_per_unit: u32) -> Self {
+        let half_extents = tile_half_extents(width, height);
+        let pixels = |half_extent: f32| ((2.0 * half_extent * pixels_per_unit as f32).ceil() as u32).max(1);
+        let (x, y, z) = (pixels(half_extents[0]), pixels(half_extents[1]), pixels(half_extents[2]));
+        let cell = |size: u32| size + 2 * GUTTER;
+
+        // Bottom and top on the first row, then sides along y and sides along x
+        let regions = [
+            ([0, 0], [x, y]),
+            ([cell(x), 0], [x, y]),
+            ([0, cell(y)], [y, z]),
+            ([cell(y), cell(y)], [y, z]),
+            ([0, cell(y) + cell(z)], [x, z]),
+            ([cell(x), cell(y) + cell(z)], [x, z]),
+        ];
+
+        TileNet {
+            dimensions: [2 * cell(x.max(y)), cell(y) + 2 * cell(z)],
+            half_extents,
+            pixels_per_unit,
+            regions,
+        }
+    }
+
+    fn region(&self, face: Face) -> ([u32; 2], [u32; 2]) {
+        self.regions[FACES.iter().position(|&f| f == face).unwrap()]
+    }
+
+    /// Texture coordinates of a point on a face
+    pub fn tex_coords(&self, face: Face, position: [f32; 3]) -> [f32; 2] {
+        let (s_axis, t_axis, _, _) = face.axes();
+        let (origin, size) = self.region(face);
+        let s = (position[s_axis] + self.half_extents[s_axis]) / (2.0 * self.half_extents[s_axis]);
+        let t = (position[t_axis] + self.half_extents[t_axis]) / (2.0 * self.half_extents[t_axis]);
+        [
+            (origin[0] + GUTTER) as f32 / self.dimensions[0] as f32
+                + s * size[0] as f32 / self.dimensions[0] as f32,
+            (origin[1] + GUTTER) as f32 / self.dimensions[1] as f32
+                + t * size[1] as f32 / self.dimensions[1] as f32,
+        ]
+    }
+
+    /// Point on the tile shown by a texel, gutters show the closest point of their face
+    pub fn texel_position(&self, x: u32, y: u32) -> Option<[f32; 3]> {
+        FACES.iter()
+            .zip(self.regions.iter())
+            .find(|&(_, &(origin, size))| {
+                x >= origin[0] && x < origin[0] + size[0] + 2 * GUTTER
+                    && y >= origin[1] && y < origin[1] + size[1] + 2 * GUTTER
+            })
+            .map(|(face, &(origin, size))| {
+                let (s_axis, t_axis, normal_axis, sign) = face.axes();
+                let s = (x as f32 + 0.5 - (origin[0] + GUTTER) as f32) / size[0] as f32;
+                let t = (y as f32 + 0.5 - (origin[1] + GUTTER) as f32) / size[1] as f32;
+
+                let mut position = [0.0; 3];
+                position[s_axis] = (2.0 * s.max(0.0).min(1.0) - 1.0) * self.half_extents[s_axis];
+                position[t_axis] = (2.0 * t.max(0.0).min(1.0) - 1.0) * self.half_extents[t_axis];
+                position[normal_axis] = sign * self.half_extents[normal_axis];
+                position
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Face, TileNet, FACES};
+
+    fn nets() -> Vec<TileNet> {
+        vec![TileNet::new(1, 1, 16), TileNet::new(2, 1, 16), TileNet::new(1, 3, 10)]
+    }
+
+    /// Texel containing the texture coordinates
+    fn texel(net: &TileNet, tex_coords: [f32; 2]) -> (u32, u32) {
+        (
+            (tex_coords[0] * net.dimensions[0] as f32) as u32,
+            (tex_coords[1] * net.dimensions[1] as f32) as u32,
+        )
+    }
+
+    /// A texel is at most one unit over pixels_per_unit wide
+    fn assert_close(net: &TileNet, a: [f32; 3], b: [f32; 3]) {
+        let tolerance = 1.0 / net.pixels_per_unit as f32;
+        for axis in 0..3 {
+            assert!((a[axis] - b[axis]).abs() <= tolerance, "{:?} and {:?} differ", a, b);
+        }
+    }
+
+    /// Point of the face at the relative coordinates in [0, 1]
+    fn face_point(net: &TileNet, face: Face, s: f32, t: f32) -> [f32; 3] {
+        let (s_axis, t_axis, normal_axis, sign) = face.axes();
+        let mut position = [0.0; 3];
+        position[s_axis] = (2.0 * s - 1.0) * net.half_extents[s_axis];
+        position[t_axis] = (2.0 * t - 1.0) * net.half_extents[t_axis];
+        position[normal_axis] = sign * net.half_extents[normal_axis];
+        position
+    }
+
+    #[test]
+    fn texel_at_tex_coords_shows_the_point() {
+        for net in nets() {
+            for &face in &FACES {
+                for i in 0..=10 {
+                    for j in 0..=10 {
+                        let position = face_point(&net, face, i as f32 / 10.0, j as f32 / 10.0);
+                        let (x, y) = texel(&net, net.tex_coords(face, position));
+                        let shown = net.texel_position(x, y).unwrap();
+                        assert_close(&net, shown, position);
+
+                        let normal_axis = face.axes().2;
+                        assert_eq!(shown[normal_axis], position[normal_axis]);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn adjacent_faces_agree_on_edges() {
+        for net in nets() {
+            for &a in &FACES {
+                for &b in &FACES {
+                    let (_, _, a_normal, a_sign) = a.axes();
+                    let (_, _, b_normal, b_sign) = b.axes();
+                    if a_normal == b_normal {
+                        continue;
+                    }
+                    let edge_axis = 3 - a_normal - b_normal;
+                    for i in 0..=10 {
+                        let mut position = [0.0; 3];
+                        position[a_normal] = a_sign * net.half_extents[a_normal];
+                        position[b_normal] = b_sign * net.half_extents[b_normal];
+                        position[edge_axis] = (i as f32 / 5.0 - 1.0) * net.half_extents[edge_axis];
+
+                        let (a_x, a_y) = texel(&net, net.tex_coords(a, position));
+                        let (b_x, b_y) = texel(&net, net.tex_coords(b, position));
+                        assert_close(
+                            &net,
+                            net.texel_position(a_x, a_y).unwrap(),
+                            net.texel_position(b_x, b_y).unwrap(),
+                        );
+                    }
+                }
+            }
+        }
+    }
 }
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::fs::File;
 use std::fmt;
 use std::io::Write;
@@ -7,6 +8,50 @@ use show_message::OkOrShow;
 use vulkano::pipeline::viewport::Viewport;
 
 pub type PhysicWorld = ::nphysics::world::World<f32>;
+
+/// Entities of the rigid bodies and sensors of the physic world by handle.
+///
+/// Handles of entities are stored in their PhysicBody and PhysicSensor components.
+/// Entries are added by PhysicBody::add and PhysicSensor::add and removed by safe_maintain.
+#[derive(Default)]
+pub struct PhysicEntities {
+    bodies: HashMap<usize, ::specs::Entity>,
+    sensors: HashMap<usize, ::specs::Entity>,
+}
+
+impl PhysicEntities {
+    pub fn insert_body(&mut self, handle: usize, entity: ::specs::Entity) {
+        self.bodies.insert(handle, entity);
+    }
+
+    pub fn insert_sensor(&mut self, handle: usize, entity: ::specs::Entity) {
+        self.sensors.insert(handle, entity);
+    }
+
+    pub fn remove_body(&mut self, handle: usize) {
+        self.bodies.remove(&handle);
+    }
+
+    pub fn remove_sensor(&mut self, handle: usize) {
+        self.sensors.remove(&handle);
+    }
+
+    pub fn body(&self, handle: usize) -> Option<::specs::Entity> {
+        self.bodies.get(&handle).cloned()
+    }
+
+    pub fn sensor(&self, handle: usize) -> Option<::specs::Entity> {
+        self.sensors.get(&handle).cloned()
+    }
+
+    pub fn object(&self, object: &::nphysics::object::WorldObject) -> Option<::specs::Entity> {
+        match *object {
+            ::nphysics::object::WorldObject::RigidBody(handle) => self.body(handle),
+            ::nphysics::object::WorldObject::Sensor(handle) => self.sensor(handle),
+        }
+    }
+}
+
 #[derive(Deref, DerefMut)]
 pub struct PlayersEntities(pub [Option<::specs::Entity>; 3]);
 #[derive(Deref, DerefMut)]
@@ -45,8 +90,23 @@ impl PlayersControllers {
             _ => None,
         }
     }
+
+    pub fn gamepad_player(&self, id: usize) -> Option<usize> {
+        self.iter()
+            .position(|c| if let &Some(::resource::Controller::Gamepad(controller_id)) = c {
+                controller_id == id
+            } else {
+                false
+            })
+    }
 }
 
+/// Gamepads that were playing when they got disconnected.
+///
+/// The slot is kept so that the gamepad gets it back when reconnected.
+#[derive(Deref, DerefMut)]
+pub struct DisconnectedGamepads(pub [Option<usize>; 3]);
+
 #[derive(Clone)]
 pub enum Controller {
     Gamepad(usize),
@@ -66,6 +126,15 @@ impl Controller {
     }
 }
 
+impl fmt::Display for Controller {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Controller::Gamepad(id) => write!(f, "Gamepad {}", id),
+            Controller::Keyboard(_) => write!(f, "Keyboard"),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct KeyboardController {
     pub mapping: Vec<(::winit::VirtualKeyCode, Control)>,
@@ -95,13 +164,138 @@ impl fmt::Display for Control {
     }
 }
 
+/// Duration of the current update, a fixed step or zero when the game is paused
 #[derive(Deref, DerefMut)]
 pub struct UpdateTime(pub f32);
 
+/// Positions of bodies before the last physic step, rendering is interpolated
+/// between them and the current positions
+#[derive(Default)]
+pub struct Interpolation {
+    /// Time accumulated toward the next update relative to the fixed step, in [0, 1]
+    pub alpha: f32,
+    pub previous: HashMap<usize, ::na::Isometry3<f32>>,
+}
+
+impl Interpolation {
+    pub fn position(&self, body: &::component::PhysicBody, physic_world: &PhysicWorld) -> ::na::Isometry3<f32> {
+        let current = body.get(physic_world).position();
+        match self.previous.get(&body.handle()) {
+            Some(previous) => ::na::Isometry3::from_parts(
+                ::na::Translation3::from_vector(
+                    previous.translation.vector * (1.0 - self.alpha) + current.translation.vector * self.alpha,
+                ),
+                previous.rotation.slerp(&current.rotation, self.alpha),
+            ),
+            None => current.clone(),
+        }
+    }
+}
+
+/// Bounds of the time scale of TimeControl
+pub const MIN_TIME_SCALE: f32 = 0.125;
+pub const MAX_TIME_SCALE: f32 = 4.0;
+
+/// Speed of the game, driven by [F5] to [F8] and the game menu.
+///
+/// Updates keep their fixed step, the scale changes how many run per frame.
+pub struct TimeControl {
+    /// Game time per real time
+    pub scale: f32,
+    /// No update runs unless a single step is requested
+    pub frozen: bool,
+    pub single_steps: usize,
+}
+
+impl Default for TimeControl {
+    fn default() -> Self {
+        TimeControl {
+            scale: 1.0,
+            frozen: false,
+            single_steps: 0,
+        }
+    }
+}
+
+impl TimeControl {
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale.max(MIN_TIME_SCALE).min(MAX_TIME_SCALE);
+    }
+
+    /// Freeze the game and advance it by one step
+    pub fn single_step(&mut self) {
+        self.frozen = true;
+        self.single_steps += 1;
+    }
+
+    pub fn key_pressed(&mut self, key: ::winit::VirtualKeyCode) {
+        match key {
+            ::winit::VirtualKeyCode::F5 => self.frozen = !self.frozen,
+            ::winit::VirtualKeyCode::F6 => self.single_step(),
+            ::winit::VirtualKeyCode::F7 => {
+                let scale = self.scale / 2.0;
+                self.set_scale(scale);
+            }
+            ::winit::VirtualKeyCode::F8 => {
+                let scale = self.scale * 2.0;
+                self.set_scale(scale);
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Progress of the players in the current level
+#[derive(Default)]
+pub struct Statistics {
+    /// Time played since the level was built
+    pub elapsed: f32,
+    pub deaths: [usize; 3],
+}
+
 #[derive(Default)]
 pub struct Text {
     pub players: [Vec<::rusttype::PositionedGlyph<'static>>; 3],
+    /// Drawn from the top left corner of the viewport of each player
+    pub huds: [Vec<::rusttype::PositionedGlyph<'static>>; 3],
     pub global: Vec<::rusttype::PositionedGlyph<'static>>,
+    /// Drawn on top of everything from the top left corner of the screen
+    pub notification: Vec<::rusttype::PositionedGlyph<'static>>,
+    /// Debug overlay drawn on top of everything
+    pub debug: Vec<::rusttype::PositionedGlyph<'static>>,
+}
+
+/// Time a notification message is displayed in seconds
+const NOTIFICATION_TIME: f32 = 5.0;
+
+/// Messages displayed for some time with the notification text
+#[derive(Default)]
+pub struct Notifications(Vec<(String, f32)>);
+
+impl Notifications {
+    pub fn push(&mut self, message: String) {
+        self.0.push((message, NOTIFICATION_TIME));
+    }
+}
+
+/// Lay out the configuration error and the recent messages, once per frame
+pub fn update_notification(world: &::specs::World, delta_time: f32) {
+    let mut lines = vec![];
+    if let Some(ref error) = world.read_resource::<::configuration::ConfigurationWatcher>().error {
+        lines.push("Configuration not reloaded:".to_string());
+        lines.extend(error.lines().filter(|line| !line.is_empty()).map(|line| line.to_string()));
+    }
+
+    let mut notifications = world.write_resource::<Notifications>();
+    for &mut (_, ref mut time) in notifications.0.iter_mut() {
+        *time -= delta_time;
+    }
+    notifications.0.retain(|&(_, time)| time > 0.0);
+    lines.extend(notifications.0.iter().flat_map(|&(ref message, _)| message.lines().map(|line| line.to_string())));
+
+    let font = world.read_resource::<Font>();
+    let text_scale = world.read_resource::<::configuration::Configuration>().text_scale;
+    world.write_resource::<Text>().notification = ::util::notification_layout(lines, &font, text_scale);
 }
 
 #[derive(Deref, DerefMut)]
@@ -114,7 +308,7 @@ impl Font {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum Mode {
     Mode1Player,
     Mode2Player,
@@ -205,7 +399,7 @@ pub struct Tiles(pub Vec<::tile::Tile>);
 #[derive(Deref, DerefMut)]
 pub struct Tubes(pub Vec<::tube::Tube>);
 
-const APP_INFO: AppInfo = AppInfo {
+pub const APP_INFO: AppInfo = AppInfo {
     name: "SESE",
     author: "thiolliere",
 };
@@ -223,6 +417,18 @@ lazy_static! {
 pub struct Save {
     vulkan_device_uuid: Option<[u8; 16]>,
     fullscreen: bool,
+    #[serde(default)]
+    borderless: bool,
+    #[serde(default)]
+    display: ::display::Display,
+    #[serde(default)]
+    level_builder: ::level::LevelBuilder,
+    /// Empty for the default name
+    #[serde(default)]
+    player_names: [String; 3],
+    /// Never read from nor written to the save file
+    #[serde(skip)]
+    in_memory: bool,
 }
 
 impl Save {
@@ -230,10 +436,100 @@ impl Save {
         File::open(SAVE_PATH.as_path())
             .ok()
             .and_then(|file| ::ron::de::from_reader(file).ok())
-            .unwrap_or(Save {
-                fullscreen: true,
-                vulkan_device_uuid: None,
-            })
+            .unwrap_or_else(Save::default_values)
+    }
+
+    /// Default save that is never written, for tests
+    #[cfg(test)]
+    pub fn in_memory() -> Self {
+        Save {
+            in_memory: true,
+            ..Save::default_values()
+        }
+    }
+
+    fn default_values() -> Self {
+        Save {
+            fullscreen: true,
+            borderless: false,
+            display: ::display::Display::default(),
+            vulkan_device_uuid: None,
+            level_builder: ::level::LevelBuilder::default(),
+            player_names: Default::default(),
+            in_memory: false,
+        }
+    }
+
+    pub fn level_builder(&self) -> &::level::LevelBuilder {
+        &self.level_builder
+    }
+
+    /// Return if changed
+    pub fn set_level_builder_lazy(&mut self, level_builder: &::level::LevelBuilder) -> bool {
+        if self.level_builder != *level_builder {
+            self.level_builder = level_builder.clone();
+            self.save();
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn player_name(&self, player: usize) -> String {
+        if self.player_names[player].is_empty() {
+            format!("Player {}", player + 1)
+        } else {
+            self.player_names[player].clone()
+        }
+    }
+
+    /// Return if changed, an empty name restores the default one
+    pub fn set_player_name_lazy(&mut self, player: usize, name: &str) -> bool {
+        let name = name.trim();
+        if self.player_names[player] != name {
+            self.player_names[player] = name.to_string();
+            self.save();
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn window_mode(&self) -> ::display::WindowMode {
+        if self.fullscreen {
+            ::display::WindowMode::Fullscreen
+        } else if self.borderless {
+            ::display::WindowMode::Borderless
+        } else {
+            ::display::WindowMode::Windowed
+        }
+    }
+
+    /// Return if changed
+    pub fn set_window_mode_lazy(&mut self, mode: ::display::WindowMode) -> bool {
+        if self.window_mode() != mode {
+            self.fullscreen = mode == ::display::WindowMode::Fullscreen;
+            self.borderless = mode == ::display::WindowMode::Borderless;
+            self.save();
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn display(&self) -> &::display::Display {
+        &self.display
+    }
+
+    /// Return if changed
+    pub fn set_display_lazy(&mut self, display: &::display::Display) -> bool {
+        if self.display != *display {
+            self.display = display.clone();
+            self.save();
+            true
+        } else {
+            false
+        }
     }
 
     pub fn vulkan_device_uuid(&self) -> &Option<[u8; 16]> {
@@ -255,6 +551,9 @@ impl Save {
     }
 
     pub fn save(&self) {
+        if self.in_memory {
+            return;
+        }
         let string = ::ron::ser::to_string(&self).unwrap();
         let mut file = File::create(SAVE_PATH.as_path()).ok_or_show(|e| {
             format!(
@@ -0,0 +1,117 @@
+use rand::SeedableRng;
+use rand::distributions::{Distribution, Range};
+use std::f32::consts::PI;
+
+/// Same seed for every run so the sky is always the same
+const SEED: [u8; 16] = [83, 69, 83, 69, 32, 115, 116, 97, 114, 102, 105, 101, 108, 100, 33, 33];
+const STARS: usize = 3000;
+const BAND_WIDTH: f32 = 0.3;
+const BAND_DARKNESS: f32 = 70.0;
+const NOISE_WIDTH: u32 = 512;
+const NOISE_HEIGHT: u32 = 128;
+const NOISE_LAYERS: u32 = 6;
+
+/// Direction of the center of a texel of the face in the vulkan cubemap layout:
+/// +X, -X, +Y, -Y, +Z, -Z
+fn texel_direction(face: usize, u: f32, v: f32) -> ::na::Vector3<f32> {
+    let direction = match face {
+        0 => ::na::Vector3::new(1.0, -v, -u),
+        1 => ::na::Vector3::new(-1.0, -v, u),
+        2 => ::na::Vector3::new(u, 1.0, v),
+        3 => ::na::Vector3::new(u, -1.0, -v),
+        4 => ::na::Vector3::new(u, -v, 1.0),
+        5 => ::na::Vector3::new(-u, -v, -1.0),
+        _ => unreachable!(),
+    };
+    direction.normalize()
+}
+
+/// Inverse of texel_direction
+fn direction_texel(direction: &::na::Vector3<f32>) -> (usize, f32, f32) {
+    let (x, y, z) = (direction[0], direction[1], direction[2]);
+    let (ax, ay, az) = (x.abs(), y.abs(), z.abs());
+    if ax >= ay && ax >= az {
+        if x > 0.0 { (0, -z / ax, -y / ax) } else { (1, z / ax, -y / ax) }
+    } else if ay >= az {
+        if y > 0.0 { (2, x / ay, z / ay) } else { (3, x / ay, -z / ay) }
+    } else if z > 0.0 {
+        (4, x / az, -y / az)
+    } else {
+        (5, -x / az, -y / az)
+    }
+}
+
+/// Generate the six faces of a grey cubemap of size x size texels.
+///
+/// Like the rest of the game it is ink on paper: a white sky with dark stars
+/// and a greyish galactic band.
+pub fn generate(size: u32) -> Vec<u8> {
+    let mut rng = ::rand::prng::XorShiftRng::from_seed(SEED);
+
+    let band_normal = ::na::Vector3::new(0.3, 1.0, 0.2).normalize();
+    let band_x = band_normal.cross(&::na::Vector3::x()).normalize();
+    let band_y = band_normal.cross(&band_x);
+
+    let noise = ::texture::generate_texture_with_rng(
+        &mut rng,
+        NOISE_WIDTH,
+        NOISE_HEIGHT,
+        NOISE_LAYERS,
+        ::image::FilterType::Triangle,
+        true,
+    );
+
+    let face_len = (size * size) as usize;
+    let mut faces = vec![255u8; 6 * face_len];
+
+    // Galactic band
+    for face in 0..6 {
+        for y in 0..size {
+            for x in 0..size {
+                let u = 2.0 * (x as f32 + 0.5) / size as f32 - 1.0;
+                let v = 2.0 * (y as f32 + 0.5) / size as f32 - 1.0;
+                let direction = texel_direction(face, u, v);
+
+                let latitude = direction.dot(&band_normal).max(-1.0).min(1.0).asin();
+                let longitude = direction.dot(&band_y).atan2(direction.dot(&band_x));
+
+                let noise_x = ((longitude / (2.0 * PI) + 0.5) * NOISE_WIDTH as f32) as u32 % NOISE_WIDTH;
+                let noise_y = (((latitude / PI) + 0.5) * (NOISE_HEIGHT - 1) as f32) as u32;
+                let density = noise[(noise_x, noise_y)].data[0] as f32 / 255.0;
+
+                let band = (-(latitude / BAND_WIDTH).powi(2)).exp();
+                let value = 255.0 - BAND_DARKNESS * band * (0.5 + density);
+
+                faces[face * face_len + (y * size + x) as usize] = value.max(0.0) as u8;
+            }
+        }
+    }
+
+    // Stars, denser in the galactic band
+    let unit_range = Range::new(-1.0f32, 1.0);
+    let angle_range = Range::new(0.0f32, 2.0 * PI);
+    let darkness_range = Range::new(80.0f32, 230.0);
+    let mut placed = 0;
+    while placed < STARS {
+        let z = unit_range.sample(&mut rng);
+        let angle = angle_range.sample(&mut rng);
+        let radius = (1.0 - z * z).sqrt();
+        let direction = ::na::Vector3::new(radius * angle.cos(), radius * angle.sin(), z);
+        let darkness = darkness_range.sample(&mut rng);
+
+        let latitude = direction.dot(&band_normal).abs().min(1.0).asin();
+        let keep = 0.3 + 0.7 * (-(latitude / BAND_WIDTH).powi(2)).exp();
+        if (unit_range.sample(&mut rng) + 1.0) / 2.0 > keep {
+            continue;
+        }
+        placed += 1;
+
+        let (face, u, v) = direction_texel(&direction);
+        let x = (((u + 1.0) / 2.0 * size as f32) as u32).min(size - 1);
+        let y = (((v + 1.0) / 2.0 * size as f32) as u32).min(size - 1);
+        let texel = &mut faces[face * face_len + (y * size + x) as usize];
+        *texel = texel.saturating_sub(darkness as u8);
+    }
+
+    faces
+}
@@ -0,0 +1,413 @@
+use app_dirs2::{app_root, AppDataType};
+use specs::Join;
+use std::fs::{self, File};
+use std::io::{ErrorKind, Write};
+use std::path::PathBuf;
+
+const FILENAME: &str = "snapshot.ron";
+
+fn path() -> Result<PathBuf, String> {
+    let mut path = app_root(AppDataType::UserData, &::resource::APP_INFO)
+        .map_err(|e| format!("Failed to get user data directory: {}", e))?;
+    path.push(FILENAME);
+    Ok(path)
+}
+
+/// A game is in progress and must be saved when leaving it
+#[derive(Default, Deref, DerefMut)]
+pub struct Playing(pub bool);
+
+#[derive(Serialize, Deserialize)]
+struct IsometryState {
+    translation: [f32; 3],
+    /// Coordinates i, j, k, w of the unit quaternion
+    rotation: [f32; 4],
+}
+
+impl IsometryState {
+    fn new(isometry: &::na::Isometry3<f32>) -> Self {
+        let t = isometry.translation.vector;
+        let q = isometry.rotation.coords;
+        IsometryState {
+            translation: [t[0], t[1], t[2]],
+            rotation: [q[0], q[1], q[2], q[3]],
+        }
+    }
+
+    fn isometry(&self) -> ::na::Isometry3<f32> {
+        let t = self.translation;
+        let q = self.rotation;
+        ::na::Isometry3::from_parts(
+            ::na::Translation3::new(t[0], t[1], t[2]),
+            ::na::UnitQuaternion::from_quaternion(::na::Quaternion::new(q[3], q[0], q[1], q[2])),
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct BodyState {
+    position: IsometryState,
+    lin_vel: [f32; 3],
+    ang_vel: [f32; 3],
+}
+
+impl BodyState {
+    fn new(body: &::nphysics::object::RigidBody<f32>) -> Self {
+        let (lin_vel, ang_vel) = (body.lin_vel(), body.ang_vel());
+        BodyState {
+            position: IsometryState::new(body.position()),
+            lin_vel: [lin_vel[0], lin_vel[1], lin_vel[2]],
+            ang_vel: [ang_vel[0], ang_vel[1], ang_vel[2]],
+        }
+    }
+
+    /// Apply the state to the body of the entity
+    fn restore(&self, entity: ::specs::Entity, world: &::specs::World) {
+        let bodies = world.read_storage::<::component::PhysicBody>();
+        let mut physic_world = world.write_resource::<::resource::PhysicWorld>();
+        let body = physic_world.mut_rigid_body(bodies.get(entity).unwrap().handle());
+        body.set_transformation(self.position.isometry());
+        body.set_lin_vel(::na::Vector3::new(self.lin_vel[0], self.lin_vel[1], self.lin_vel[2]));
+        body.set_ang_vel(::na::Vector3::new(self.ang_vel[0], self.ang_vel[1], self.ang_vel[2]));
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct TileState {
+    position: IsometryState,
+    size: ::tile::TileSize,
+    width: f32,
+    height: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TubeState {
+    position: IsometryState,
+    shape: ::tube::Shape,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RocketLauncherState {
+    position: IsometryState,
+    timer: f32,
+}
+
+/// State of a game in progress.
+///
+/// The maze, tiles and tubes are stored as generated because their
+/// generation is random.
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    level_builder: ::level::LevelBuilder,
+    mode: ::resource::Mode,
+    maze_size: [isize; 3],
+    maze_walls: Vec<[isize; 3]>,
+    tiles: Vec<TileState>,
+    tubes: Vec<TubeState>,
+    elapsed: f32,
+    deaths: [usize; 3],
+    explored: [Vec<[isize; 3]>; 3],
+    players: [Option<BodyState>; 3],
+    rockets: Vec<BodyState>,
+    mines: Vec<BodyState>,
+    targets: Vec<[f32; 3]>,
+    rocket_launchers: Vec<RocketLauncherState>,
+}
+
+impl Snapshot {
+    pub fn new(world: &::specs::World) -> Self {
+        let physic_world = world.read_resource::<::resource::PhysicWorld>();
+        let maze_map = world.read_resource::<::minimap::MazeMap>();
+        let statistics = world.read_resource::<::resource::Statistics>();
+        let explored = world.read_resource::<::minimap::Explored>();
+        let bodies = world.read_storage::<::component::PhysicBody>();
+        let sensors = world.read_storage::<::component::PhysicSensor>();
+        let body_state = |body: &::component::PhysicBody| BodyState::new(body.get(&physic_world));
+        let cell = |cell: &::na::Vector3<isize>| [cell[0], cell[1], cell[2]];
+
+        let mut players = [None, None, None];
+        for (state, entity) in players.iter_mut().zip(world.read_resource::<::resource::PlayersEntities>().iter()) {
+            *state = entity.and_then(|entity| bodies.get(entity)).map(&body_state);
+        }
+
+        let mut explored_cells = [vec![], vec![], vec![]];
+        for (cells, explored) in explored_cells.iter_mut().zip(explored.cells.iter()) {
+            *cells = explored.iter().map(&cell).collect();
+        }
+
+        Snapshot {
+            level_builder: world.read_resource::<::resource::Save>().level_builder().clone(),
+            mode: *world.read_resource::<::resource::Mode>(),
+            maze_size: cell(&maze_map.maze.size()),
+            maze_walls: maze_map.maze.walls.iter().map(&cell).collect(),
+            tiles: world.read_resource::<::resource::Tiles>().0.iter()
+                .map(|tile| TileState {
+                    position: IsometryState::new(&tile.position),
+                    size: tile.size,
+                    width: tile.width,
+                    height: tile.height,
+                })
+                .collect(),
+            tubes: world.read_resource::<::resource::Tubes>().iter()
+                .map(|tube| TubeState {
+                    position: IsometryState::new(&tube.position),
+                    shape: tube.shape,
+                })
+                .collect(),
+            elapsed: statistics.elapsed,
+            deaths: statistics.deaths,
+            explored: explored_cells,
+            players,
+            rockets: (&world.read_storage::<::component::RocketControl>(), &bodies).join()
+                .map(|(_, body)| body_state(body))
+                .collect(),
+            mines: (&world.read_storage::<::component::MineControl>(), &bodies).join()
+                .map(|(_, body)| body_state(body))
+                .collect(),
+            targets: (&world.read_storage::<::component::Target>(), &sensors).join()
+                .map(|(_, sensor)| {
+                    let position = sensor.position(&physic_world).translation.vector;
+                    [position[0], position[1], position[2]]
+                })
+                .collect(),
+            rocket_launchers: world.read_storage::<::component::RocketLauncher>().join()
+                .map(|rocket_launcher| RocketLauncherState {
+                    position: IsometryState::new(&rocket_launcher.position),
+                    timer: rocket_launcher.timer,
+                })
+                .collect(),
+        }
+    }
+
+    /// A snapshot has been saved
+    pub fn exists() -> Result<bool, String> {
+        Ok(path()?.exists())
+    }
+
+    pub fn load() -> Result<Self, String> {
+        let path = path()?;
+        let file = File::open(&path)
+            .map_err(|e| format!("Failed to open snapshot {}: {}", path.display(), e))?;
+        ::ron::de::from_reader(file)
+            .map_err(|e| format!("Failed to parse snapshot {}: {}", path.display(), e))
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let string = ::ron::ser::to_string(&self)
+            .map_err(|e| format!("Failed to serialize snapshot: {}", e))?;
+        let path = path()?;
+        let mut file = File::create(&path).map_err(|e| {
+            format!("Failed to create snapshot at {}: {}", path.display(), e)
+        })?;
+        file.write_all(string.as_bytes()).map_err(|e| {
+            format!("Failed to write to snapshot {}: {}", path.display(), e)
+        })
+    }
+
+    /// Remove the saved snapshot if any
+    pub fn delete() -> Result<(), String> {
+        let path = path()?;
+        match fs::remove_file(&path) {
+            Err(ref e) if e.kind() != ErrorKind::NotFound => {
+                Err(format!("Failed to delete snapshot {}: {}", path.display(), e))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Replace the level and its entities by the snapshot ones
+    pub fn restore(&self, world: &mut ::specs::World) {
+        use world_action::WorldAction;
+
+        world.maintain();
+        world.delete_all();
+
+        world.write_resource::<::resource::PlayersEntities>().0 = [None; 3];
+        *world.write_resource::<::resource::Mode>() = self.mode;
+        world.reset_for_mode();
+        world.write_resource::<::resource::Save>().set_level_builder_lazy(&self.level_builder);
+
+        let vector = |v: &[isize; 3]| ::na::Vector3::new(v[0], v[1], v[2]);
+        let mut maze = ::maze::Maze::new_rectangle(vector(&self.maze_size));
+        maze.walls.extend(self.maze_walls.iter().map(&vector));
+
+        let tiles = self.tiles.iter()
+            .map(|tile| ::tile::Tile {
+                position: tile.position.isometry(),
+                size: tile.size,
+                width: tile.width,
+                height: tile.height,
+            })
+            .collect();
+        let tubes = self.tubes.iter()
+            .map(|tube| ::tube::Tube {
+                position: tube.position.isometry(),
+                shape: tube.shape,
+            })
+            .collect();
+        self.level_builder.build_structure(&maze, tiles, tubes, world);
+
+        {
+            let mut statistics = world.write_resource::<::resource::Statistics>();
+            statistics.elapsed = self.elapsed;
+            statistics.deaths = self.deaths;
+        }
+        {
+            let mut explored = world.write_resource::<::minimap::Explored>();
+            for (cells, saved) in explored.cells.iter_mut().zip(self.explored.iter()) {
+                cells.extend(saved.iter().map(&vector));
+            }
+        }
+
+        for (player, state) in self.players.iter().enumerate() {
+            if let Some(ref state) = *state {
+                let entity = ::entity::create_player(player, ::na::zero(), world);
+                state.restore(entity, world);
+            }
+        }
+
+        for state in &self.rockets {
+            let rocket = ::entity::create_rocket(state.position.isometry(), world);
+            state.restore(rocket, world);
+        }
+        for state in &self.mines {
+            let mine = ::entity::create_mine(::na::zero(), world);
+            state.restore(mine, world);
+        }
+        for target in &self.targets {
+            ::entity::create_target(::na::Vector3::new(target[0], target[1], target[2]), world);
+        }
+        for state in &self.rocket_launchers {
+            ::entity::create_rocket_launcher(state.position.isometry(), state.timer, world);
+        }
+    }
+}
+
+/// Save the game if one is in progress, errors are notified
+pub fn save_playing(world: &::specs::World) {
+    if world.read_resource::<Playing>().0 {
+        if let Err(e) = Snapshot::new(world).save() {
+            world.write_resource::<::resource::Notifications>().push(e);
+        }
+    }
+}
+
+/// Stop saving the game once all the targets are captured
+pub fn complete_level(world: &::specs::World) {
+    if world.read_resource::<Playing>().0 && world.read_storage::<::component::Target>().join().next().is_none() {
+        world.write_resource::<Playing>().0 = false;
+        let elapsed = world.read_resource::<::resource::Statistics>().elapsed as usize;
+        let message = match Snapshot::delete() {
+            Ok(()) => format!("Level completed in {}:{:02}", elapsed / 60, elapsed % 60),
+            Err(e) => e,
+        };
+        world.write_resource::<::resource::Notifications>().push(message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BodyState, Snapshot};
+    use specs::{Join, World};
+
+    fn create_world() -> World {
+        let mut world = ::create_world(::configuration::Configuration::built_in().unwrap());
+        world.add_resource(::resource::Save::in_memory());
+        world
+    }
+
+    fn assert_close(a: &[f32], b: &[f32]) {
+        assert_eq!(a.len(), b.len());
+        for (x, y) in a.iter().zip(b.iter()) {
+            assert!((x - y).abs() < 1e-5, "{:?} and {:?} differ", a, b);
+        }
+    }
+
+    fn assert_body(a: &BodyState, b: &BodyState) {
+        assert_close(&a.position.translation, &b.position.translation);
+        assert_close(&a.position.rotation, &b.position.rotation);
+        assert_close(&a.lin_vel, &b.lin_vel);
+        assert_close(&a.ang_vel, &b.ang_vel);
+    }
+
+    fn assert_bodies(a: &[BodyState], b: &[BodyState]) {
+        assert_eq!(a.len(), b.len());
+        for (a, b) in a.iter().zip(b.iter()) {
+            assert_body(a, b);
+        }
+    }
+
+    fn sorted(cells: &[[isize; 3]]) -> Vec<[isize; 3]> {
+        let mut cells = cells.to_vec();
+        cells.sort();
+        cells
+    }
+
+    #[test]
+    fn save_load_round_trip() {
+        let mut world = create_world();
+        let level_builder = world.read_resource::<::resource::Save>().level_builder().clone();
+        level_builder.build(&mut world);
+
+        // Moving player and rocket, launchers about to fire and some explored cells
+        let player = ::entity::create_player(0, ::na::Vector3::new(1.0, 2.0, 3.0), &world);
+        let rocket = ::entity::create_rocket(
+            ::na::Isometry3::new(::na::Vector3::new(4.0, 5.0, 6.0), ::na::Vector3::new(0.0, 1.0, 0.0)),
+            &world,
+        );
+        {
+            let bodies = world.read_storage::<::component::PhysicBody>();
+            let mut physic_world = world.write_resource::<::resource::PhysicWorld>();
+            for &(entity, velocity) in &[(player, 1.0), (rocket, -2.0)] {
+                let body = physic_world.mut_rigid_body(bodies.get(entity).unwrap().handle());
+                body.set_lin_vel(::na::Vector3::new(velocity, 0.5, 0.0));
+                body.set_ang_vel(::na::Vector3::new(0.0, 0.0, velocity));
+            }
+        }
+        for (i, rocket_launcher) in (&mut world.write_storage::<::component::RocketLauncher>()).join().enumerate() {
+            rocket_launcher.timer = 0.5 + i as f32;
+        }
+        {
+            let mut explored = world.write_resource::<::minimap::Explored>();
+            explored.cells[0].insert(::na::Vector3::new(1, 2, 3));
+            explored.cells[0].insert(::na::Vector3::new(1, 2, 4));
+        }
+        world.maintain();
+
+        let saved = Snapshot::new(&world);
+        let loaded: Snapshot = ::ron::de::from_str(&::ron::ser::to_string(&saved).unwrap()).unwrap();
+        let mut restored_world = create_world();
+        loaded.restore(&mut restored_world);
+        restored_world.maintain();
+        let restored = Snapshot::new(&restored_world);
+
+        assert_eq!(restored.maze_size, saved.maze_size);
+        assert_eq!(sorted(&restored.maze_walls), sorted(&saved.maze_walls));
+        for (restored, saved) in restored.explored.iter().zip(saved.explored.iter()) {
+            assert_eq!(sorted(restored), sorted(saved));
+        }
+        assert_eq!(restored.elapsed, saved.elapsed);
+        assert_eq!(restored.deaths, saved.deaths);
+
+        for (restored, saved) in restored.players.iter().zip(saved.players.iter()) {
+            match (restored, saved) {
+                (&Some(ref restored), &Some(ref saved)) => assert_body(restored, saved),
+                (&None, &None) => (),
+                _ => panic!("players differ"),
+            }
+        }
+        assert_bodies(&restored.rockets, &saved.rockets);
+        assert_bodies(&restored.mines, &saved.mines);
+
+        assert_eq!(restored.targets.len(), saved.targets.len());
+        for (restored, saved) in restored.targets.iter().zip(saved.targets.iter()) {
+            assert_close(restored, saved);
+        }
+
+        assert_eq!(restored.rocket_launchers.len(), saved.rocket_launchers.len());
+        for (restored, saved) in restored.rocket_launchers.iter().zip(saved.rocket_launchers.iter()) {
+            assert_close(&restored.position.translation, &saved.position.translation);
+            assert_eq!(restored.timer, saved.timer);
+        }
+    }
+}
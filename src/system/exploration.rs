@@ -0,0 +1,29 @@
+pub struct ExplorationSystem;
+
+impl<'a> ::specs::System<'a> for ExplorationSystem {
+    type SystemData = (
+        ::specs::ReadStorage<'a, ::component::PhysicBody>,
+        ::specs::ReadExpect<'a, ::resource::PlayersEntities>,
+        ::specs::ReadExpect<'a, ::resource::PhysicWorld>,
+        ::specs::ReadExpect<'a, ::minimap::MazeMap>,
+        ::specs::WriteExpect<'a, ::minimap::Explored>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            bodies,
+            players_entities,
+            physic_world,
+            maze_map,
+            mut explored,
+        ): Self::SystemData,
+    ) {
+        for (player, entity) in players_entities.iter().enumerate() {
+            if let Some(body) = entity.and_then(|entity| bodies.get(entity)) {
+                let position = body.get(&physic_world).position().translation.vector;
+                explored.visit(player, maze_map.cell(&position));
+            }
+        }
+    }
+}
@@ -4,3 +4,6 @@ pub mod player_killer;
 pub mod rocket_launcher;
 pub mod closest_player;
 pub mod player_creator;
+pub mod trail;
+pub mod statistics;
+pub mod exploration;
@@ -1,8 +1,11 @@
 use nphysics::object::WorldObject;
-use ncollide::events::ProximityEvent;
+use ncollide::events::{ContactEvent, ProximityEvent};
 use ncollide::query::Proximity;
 use specs::Join;
 
+/// Dampings are the fraction of velocity kept after this duration
+const DAMPING_TIME: f32 = 1.0 / 60.0;
+
 pub struct PhysicSystem;
 
 impl<'a> ::specs::System<'a> for PhysicSystem {
@@ -15,7 +18,11 @@ impl<'a> ::specs::System<'a> for PhysicSystem {
         ::specs::WriteStorage<'a, ::component::Contactor>,
         ::specs::WriteStorage<'a, ::component::Proximitor>,
         ::specs::ReadExpect<'a, ::resource::UpdateTime>,
+        ::specs::ReadExpect<'a, ::configuration::Configuration>,
         ::specs::WriteExpect<'a, ::resource::PhysicWorld>,
+        ::specs::ReadExpect<'a, ::resource::PhysicEntities>,
+        ::specs::WriteExpect<'a, ::event::Events>,
+        ::specs::WriteExpect<'a, ::resource::Interpolation>,
     );
 
     fn run(
@@ -29,17 +36,28 @@ impl<'a> ::specs::System<'a> for PhysicSystem {
             mut contactors,
             mut proximitors,
             update_time,
+            configuration,
             mut physic_world,
+            physic_entities,
+            mut events,
+            mut interpolation,
         ): Self::SystemData,
     ) {
+        let damping = |damping: f32| damping.powf(update_time.0 / DAMPING_TIME);
+
+        interpolation.previous.clear();
+        for body in bodies.join() {
+            interpolation.previous.insert(body.handle(), body.get(&physic_world).position().clone());
+        }
+
         for (flight_control, body) in (&flight_controls, &mut bodies).join() {
             let body = body.get_mut(&mut physic_world);
             let orientation = body.position().rotation;
 
             let ang_vel = body.ang_vel();
             let lin_vel = body.lin_vel();
-            body.set_ang_vel_internal(flight_control.ang_damping * ang_vel);
-            body.set_lin_vel_internal(flight_control.lin_damping * lin_vel);
+            body.set_ang_vel_internal(damping(flight_control.ang_damping) * ang_vel);
+            body.set_lin_vel_internal(damping(flight_control.lin_damping) * lin_vel);
 
             body.clear_forces();
             body.append_ang_force(
@@ -59,22 +77,22 @@ impl<'a> ::specs::System<'a> for PhysicSystem {
             let body = body.get_mut(&mut physic_world);
 
             let lin_vel = body.lin_vel();
-            body.set_lin_vel_internal(::CFG.rocket_control_lin_damping * lin_vel);
+            body.set_lin_vel_internal(damping(configuration.rocket_control_lin_damping) * lin_vel);
 
             body.clear_forces();
             let direction = closest_player.vector.map_or(::na::zero(), |v| v.normalize());
-            body.append_lin_force(direction * ::CFG.rocket_control_force);
+            body.append_lin_force(direction * configuration.rocket_control_force);
         }
 
         for (_, body, closest_player) in (&mine_controls, &mut bodies, &closest_players).join() {
             let body = body.get_mut(&mut physic_world);
 
             let lin_vel = body.lin_vel();
-            body.set_lin_vel_internal(::CFG.rocket_control_lin_damping * lin_vel);
+            body.set_lin_vel_internal(damping(configuration.rocket_control_lin_damping) * lin_vel);
 
             body.clear_forces();
             if let Some(v) = closest_player.vector {
-                let force = ::CFG.mine_control_max_force - v.norm()*::CFG.mine_control_coef_force;
+                let force = configuration.mine_control_max_force - v.norm()*configuration.mine_control_coef_force;
                 if force >= 0.0 {
                     body.append_lin_force(force*v.normalize());
                 }
@@ -88,23 +106,13 @@ impl<'a> ::specs::System<'a> for PhysicSystem {
             proximitor.intersections.clear();
         }
 
-        let mut remaining_to_update = update_time.0;
-        while remaining_to_update > ::CFG.physic_min_step_time {
-            let step = remaining_to_update.min(::CFG.physic_max_step_time);
-            remaining_to_update -= step;
-            physic_world.step(step);
+        if update_time.0 > 0.0 {
+            physic_world.step(update_time.0);
 
             for (co1, co2, mut contact) in physic_world.collision_world().contacts() {
-                let (entity_1, entity_2) = match (&co1.data, &co2.data) {
-                    (&WorldObject::RigidBody(w1), &WorldObject::RigidBody(w2)) => {
-                        let e1 = physic_world.rigid_body(w1);
-                        let e2 = physic_world.rigid_body(w2);
-                        (
-                            ::component::PhysicBody::entity(e1),
-                            ::component::PhysicBody::entity(e2),
-                        )
-                    }
-                    _ => unreachable!(),
+                let (entity_1, entity_2) = match (physic_entities.object(&co1.data), physic_entities.object(&co2.data)) {
+                    (Some(entity_1), Some(entity_2)) => (entity_1, entity_2),
+                    _ => continue,
                 };
 
                 if let Some(contactor) = contactors.get_mut(entity_1) {
@@ -117,6 +125,33 @@ impl<'a> ::specs::System<'a> for PhysicSystem {
                 }
             }
 
+            for event in physic_world.collision_world().contact_events() {
+                if let &ContactEvent::Started(co1, co2) = event {
+                    let co1 = physic_world
+                        .collision_world()
+                        .collision_object(co1)
+                        .map(|c| &c.data);
+                    let co2 = physic_world
+                        .collision_world()
+                        .collision_object(co2)
+                        .map(|c| &c.data);
+
+                    if let (Some(&WorldObject::RigidBody(w1)), Some(&WorldObject::RigidBody(w2))) = (co1, co2) {
+                        for &(wall, body) in &[(w1, w2), (w2, w1)] {
+                            let (wall_body, body_body) = (physic_world.rigid_body(wall), physic_world.rigid_body(body));
+                            if is_wall(wall_body) && !is_wall(body_body) {
+                                if let Some(entity) = physic_entities.body(body) {
+                                    events.push(::event::Event::WallHit {
+                                        entity,
+                                        position: body_body.position().translation.vector,
+                                    });
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+
             for event in physic_world.collision_world().proximity_events() {
                 if let &ProximityEvent {
                     co1,
@@ -135,36 +170,22 @@ impl<'a> ::specs::System<'a> for PhysicSystem {
                         .map(|c| &c.data);
 
                     if let (Some(co1), Some(co2)) = (co1, co2) {
-                        // we can't just get e1 and e2 and check for each if there is a proximitor
-                        // because the rigid body of eX may be involve in a proximity even if the
-                        // proximitor is associated to eX sensor
-                        match (co1, co2) {
-                            (&WorldObject::Sensor(w1), &WorldObject::RigidBody(w2)) => {
-                                let e1 = ::component::PhysicSensor::entity(physic_world.sensor(w1));
-                                let e2 = ::component::PhysicBody::entity(physic_world.rigid_body(w2));
-                                if let Some(proximitor) = proximitors.get_mut(e1) {
-                                    proximitor.intersections.push(e2);
-                                }
+                        let (e1, e2) = match (physic_entities.object(co1), physic_entities.object(co2)) {
+                            (Some(e1), Some(e2)) => (e1, e2),
+                            _ => continue,
+                        };
+
+                        // Only the sensor of an entity reports to its proximitor, its
+                        // rigid body may be involved in a proximity as well
+                        if let &WorldObject::Sensor(_) = co1 {
+                            if let Some(proximitor) = proximitors.get_mut(e1) {
+                                proximitor.intersections.push(e2);
                             }
-                            (&WorldObject::RigidBody(w1), &WorldObject::Sensor(w2)) => {
-                                let e1 = ::component::PhysicBody::entity(physic_world.rigid_body(w1));
-                                let e2 = ::component::PhysicSensor::entity(physic_world.sensor(w2));
-                                if let Some(proximitor) = proximitors.get_mut(e2) {
-                                    proximitor.intersections.push(e1);
-                                }
-                            }
-                            (&WorldObject::Sensor(w1), &WorldObject::Sensor(w2)) => {
-                                let e1 = ::component::PhysicSensor::entity(physic_world.sensor(w1));
-                                let e2 = ::component::PhysicSensor::entity(physic_world.sensor(w2));
-                                println!("{:?} {:?}", e1, e2);
-                                if let Some(proximitor) = proximitors.get_mut(e2) {
-                                    proximitor.intersections.push(e1);
-                                }
-                                if let Some(proximitor) = proximitors.get_mut(e1) {
-                                    proximitor.intersections.push(e2);
-                                }
+                        }
+                        if let &WorldObject::Sensor(_) = co2 {
+                            if let Some(proximitor) = proximitors.get_mut(e2) {
+                                proximitor.intersections.push(e1);
                             }
-                            _ => unreachable!(),
                         }
                     }
                 }
@@ -172,3 +193,9 @@ impl<'a> ::specs::System<'a> for PhysicSystem {
         }
     }
 }
+
+fn is_wall(body: &::nphysics::object::RigidBody<f32>) -> bool {
+    body.collision_groups()
+        .as_collision_groups()
+        .is_member_of(::entity::Group::Wall as usize)
+}
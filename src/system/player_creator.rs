@@ -33,7 +33,10 @@ impl<'a> ::specs::System<'a> for PlayerCreatorSystem {
                         -10+player as isize *2,
                         -10,
                     );
-                    ::entity::create_player(::util::to_world(&player_pos, 1.0), world);
+                    let position = ::util::to_world(&player_pos, 1.0);
+                    let entity = ::entity::create_player(player, position, world);
+                    world.write_resource::<::event::Events>()
+                        .push(::event::Event::PlayerSpawned { player: entity, position });
                 });
             }
         }
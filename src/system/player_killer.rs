@@ -7,6 +7,9 @@ impl<'a> ::specs::System<'a> for PlayerKillerSystem {
         ::specs::ReadStorage<'a, ::component::Player>,
         ::specs::ReadStorage<'a, ::component::Contactor>,
         ::specs::ReadStorage<'a, ::component::PlayerKiller>,
+        ::specs::ReadStorage<'a, ::component::PhysicBody>,
+        ::specs::ReadExpect<'a, ::resource::PhysicWorld>,
+        ::specs::WriteExpect<'a, ::event::Events>,
         ::specs::Entities<'a>,
     );
 
@@ -16,16 +19,22 @@ impl<'a> ::specs::System<'a> for PlayerKillerSystem {
             players,
             contactors,
             player_killers,
+            bodies,
+            physic_world,
+            mut events,
             entities,
         ): Self::SystemData,
     ) {
-        for (_, contactor, entity) in (&player_killers, &contactors, &*entities).join() {
+        for (_, contactor, body, entity) in (&player_killers, &contactors, &bodies, &*entities).join() {
             if !contactor.contacts.is_empty() {
+                let position = body.get(&physic_world).position().translation.vector;
+                events.push(::event::Event::Explosion { position });
                 for player in contactor.contacts.iter()
                     .map(|&(entity, _)| entity)
                     .filter(|&entity| players.get(entity).is_some())
                     .collect::<Vec<_>>()
                 {
+                    events.push(::event::Event::PlayerKilled { player, position });
                     entities.delete(player).unwrap();
                 }
                 entities.delete(entity).unwrap();
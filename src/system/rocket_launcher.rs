@@ -6,7 +6,9 @@ impl<'a> ::specs::System<'a> for RocketLauncherSystem {
     type SystemData = (
         ::specs::WriteStorage<'a, ::component::RocketLauncher>,
         ::specs::ReadExpect<'a, ::resource::UpdateTime>,
+        ::specs::ReadExpect<'a, ::configuration::Configuration>,
         ::specs::ReadExpect<'a, ::specs::LazyUpdate>,
+        ::specs::WriteExpect<'a, ::event::Events>,
     );
 
     fn run(
@@ -14,17 +16,20 @@ impl<'a> ::specs::System<'a> for RocketLauncherSystem {
         (
             mut rocket_launchers,
             update_time,
+            configuration,
             lazy_update,
+            mut events,
         ): Self::SystemData,
     ) {
         for rocket_launcher in (&mut rocket_launchers).join() {
             rocket_launcher.timer -= update_time.0;
             if rocket_launcher.timer <= 0.0 {
                 let position = rocket_launcher.position;
+                events.push(::event::Event::RocketLaunched { position });
                 lazy_update.exec(move |world| {
                     ::entity::create_rocket(position, world);
                 });
-                rocket_launcher.timer = ::CFG.rocket_launcher_timer;
+                rocket_launcher.timer = configuration.rocket_launcher_timer;
             }
         }
     }
@@ -0,0 +1,30 @@
+pub struct StatisticsSystem;
+
+impl<'a> ::specs::System<'a> for StatisticsSystem {
+    type SystemData = (
+        ::specs::ReadExpect<'a, ::event::Events>,
+        ::specs::ReadExpect<'a, ::resource::UpdateTime>,
+        ::specs::ReadExpect<'a, ::resource::PlayersEntities>,
+        ::specs::WriteExpect<'a, ::resource::Statistics>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            events,
+            update_time,
+            players_entities,
+            mut statistics,
+        ): Self::SystemData,
+    ) {
+        statistics.elapsed += update_time.0;
+
+        for event in events.iter() {
+            if let ::event::Event::PlayerKilled { player: entity, .. } = *event {
+                if let Some(player) = players_entities.iter().position(|&e| e == Some(entity)) {
+                    statistics.deaths[player] += 1;
+                }
+            }
+        }
+    }
+}
@@ -8,7 +8,10 @@ impl<'a> ::specs::System<'a> for TargetSystem {
         ::specs::ReadStorage<'a, ::component::Player>,
         ::specs::ReadStorage<'a, ::component::PhysicBody>,
         ::specs::ReadExpect<'a, ::resource::PhysicWorld>,
+        ::specs::ReadExpect<'a, ::resource::PhysicEntities>,
         ::specs::ReadExpect<'a, ::resource::Mode>,
+        ::specs::ReadExpect<'a, ::configuration::Configuration>,
+        ::specs::WriteExpect<'a, ::event::Events>,
         ::specs::Entities<'a>,
     );
 
@@ -18,7 +21,10 @@ impl<'a> ::specs::System<'a> for TargetSystem {
             players,
             bodies,
             physic_world,
+            physic_entities,
             mode,
+            configuration,
+            mut events,
             entities,
         ): Self::SystemData,
     ) {
@@ -39,7 +45,7 @@ impl<'a> ::specs::System<'a> for TargetSystem {
 
         let (shape, position) = match mode.number_of_player() {
             1 => {
-                let shape = ::ncollide::shape::Ball::new(::CFG.ball_radius);
+                let shape = ::ncollide::shape::Ball::new(configuration.ball_radius);
                 let position = (&players, &bodies).join().next().unwrap().1.get(&physic_world).position();
                 (shape, position)
             },
@@ -59,10 +65,11 @@ impl<'a> ::specs::System<'a> for TargetSystem {
             .next()
             .is_none()
         {
-            for entity in physic_world.collision_world().interferences_with_aabb(&shape.aabb(position), target_group.as_collision_groups())
+            for (entity, target_position) in physic_world.collision_world().interferences_with_aabb(&shape.aabb(position), target_group.as_collision_groups())
                 .filter(|co| ::ncollide::query::proximity(&co.position, &*co.shape, &position, &shape, 0.0)  == ::ncollide::query::Proximity::Intersecting)
-                .map(|co| ::component::physic_world_object_entity(&co.data, &physic_world))
+                .filter_map(|co| physic_entities.object(&co.data).map(|entity| (entity, co.position.translation.vector)))
             {
+                events.push(::event::Event::TargetCaptured { position: target_position });
                 entities.delete(entity).unwrap();
             }
         }
@@ -0,0 +1,42 @@
+use specs::Join;
+
+/// Sample the interpolated positions, run once per frame after the interpolation is set
+pub struct TrailSystem;
+
+impl<'a> ::specs::System<'a> for TrailSystem {
+    type SystemData = (
+        ::specs::ReadStorage<'a, ::component::PhysicBody>,
+        ::specs::WriteStorage<'a, ::component::Trail>,
+        ::specs::ReadExpect<'a, ::resource::UpdateTime>,
+        ::specs::ReadExpect<'a, ::debug::UpdateSteps>,
+        ::specs::ReadExpect<'a, ::resource::Interpolation>,
+        ::specs::ReadExpect<'a, ::configuration::Configuration>,
+        ::specs::ReadExpect<'a, ::resource::PhysicWorld>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            bodies,
+            mut trails,
+            update_time,
+            update_steps,
+            interpolation,
+            configuration,
+            physic_world,
+        ): Self::SystemData,
+    ) {
+        let elapsed = update_time.0 * update_steps.0 as f32;
+        for (body, trail) in (&bodies, &mut trails).join() {
+            trail.timer -= elapsed;
+            if trail.timer > 0.0 {
+                continue;
+            }
+            trail.timer = configuration.trail_period;
+
+            let position = interpolation.position(body, &physic_world).translation.vector;
+            trail.positions.push_front(position);
+            trail.positions.truncate(configuration.trail_length);
+        }
+    }
+}
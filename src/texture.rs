@@ -1,5 +1,279 @@
+use rand::Rng;
 use rand::distributions::{Distribution, Standard};
 
+/// Lattice cells per unit of the coarsest octave of themed textures
+const THEME_FREQUENCY: f32 = 2.0;
+const THEME_OCTAVES: usize = 5;
+/// Rings per unit
+const WOOD_RINGS: f32 = 6.0;
+const WOOD_TURBULENCE: f32 = 0.6;
+/// Distance of the heart of the trunk under the tile
+const WOOD_HEART: f32 = 2.0;
+/// Flagstones per unit
+const FLAGSTONE_FREQUENCY: f32 = 2.0;
+/// Width of the joints between flagstones in units
+const FLAGSTONE_JOINT: f32 = 0.03;
+
+const GRADIENTS: [[f32; 3]; 12] = [
+    [1.0, 1.0, 0.0], [-1.0, 1.0, 0.0], [1.0, -1.0, 0.0], [-1.0, -1.0, 0.0],
+    [1.0, 0.0, 1.0], [-1.0, 0.0, 1.0], [1.0, 0.0, -1.0], [-1.0, 0.0, -1.0],
+    [0.0, 1.0, 1.0], [0.0, -1.0, 1.0], [0.0, 1.0, -1.0], [0.0, -1.0, -1.0],
+];
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize, EnumIterator)]
+pub enum Theme {
+    /// Multi-octave white noise
+    Noise,
+    Rock,
+    Wood,
+    Flagstone,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::Noise
+    }
+}
+
+impl Theme {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Theme::Noise => "Noise",
+            Theme::Rock => "Rock",
+            Theme::Wood => "Wood",
+            Theme::Flagstone => "Flagstone",
+        }
+    }
+
+    /// Amount of black at a point of the tile
+    ///
+    /// Textures are solid so that faces match where they touch once folded
+    fn grey<R: Rng>(&self, rng: &mut R, pixels_per_unit: u32, layers: u32) -> Box<Fn([f32; 3]) -> f32> {
+        match *self {
+            Theme::Noise => {
+                // Like generate_texture: equal octaves down to the pixel
+                let octaves = (layers - 1) as usize;
+                let frequency = pixels_per_unit as f32 / 2_u32.pow(layers - 2) as f32;
+                let fractal = Fractal::new(rng, Lattice::Value, frequency, octaves, 1.0);
+                Box::new(move |position| 0.5 + 0.5 * fractal.sample(position))
+            }
+            Theme::Rock => {
+                let fractal = Fractal::new(rng, Lattice::Gradient, THEME_FREQUENCY, THEME_OCTAVES, 0.5);
+                Box::new(move |position| 0.4 + 0.3 * fractal.sample(position))
+            }
+            Theme::Wood => {
+                let fractal = Fractal::new(rng, Lattice::Gradient, THEME_FREQUENCY, THEME_OCTAVES, 0.5);
+                let grain = Noise::new(rng, Lattice::Gradient);
+                Box::new(move |position| {
+                    // The trunk is along x under the tile
+                    let distance = (position[1].powi(2) + (position[2] + WOOD_HEART).powi(2)).sqrt();
+                    let rings = distance * WOOD_RINGS + WOOD_TURBULENCE * fractal.sample(position);
+                    let ring = 0.5 + 0.5 * (2.0 * ::std::f32::consts::PI * rings).sin();
+                    let grain = grain.sample([position[0], position[1] * 40.0, position[2] * 40.0]);
+                    0.3 + 0.25 * ring.powi(3) + 0.08 * grain
+                })
+            }
+            Theme::Flagstone => {
+                let voronoi = Voronoi::new(rng, FLAGSTONE_FREQUENCY);
+                let fractal = Fractal::new(rng, Lattice::Gradient, 2.0 * THEME_FREQUENCY, THEME_OCTAVES, 0.5);
+                Box::new(move |position| {
+                    let (shade, joint_distance) = voronoi.nearest(position);
+                    let joint = 1.0 - smoothstep(FLAGSTONE_JOINT / 2.0, FLAGSTONE_JOINT, joint_distance);
+                    let stone = 0.25 + 0.25 * shade + 0.1 * fractal.sample(position);
+                    stone + (0.9 - stone) * joint
+                })
+            }
+        }
+    }
+}
+
+/// Generate the texture of the unwrapped tile, continuous across its edges
+pub fn generate_tile_texture(
+    theme: Theme,
+    net: &::obj::TileNet,
+    layers: u32,
+) -> ::image::ImageBuffer<::image::Luma<u8>, Vec<u8>> {
+    let theme_grey = theme.grey(&mut ::rand::thread_rng(), net.pixels_per_unit, layers);
+
+    ::image::ImageBuffer::from_fn(net.dimensions[0], net.dimensions[1], |x, y| {
+        let grey = match net.texel_position(x, y) {
+            Some(position) => theme_grey(position),
+            None => 0.5,
+        };
+        ::image::Luma { data: [(grey.max(0.0).min(1.0) * 255.0) as u8] }
+    })
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).max(0.0).min(1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Pseudo random value in [0, 1) of a point of the integer lattice
+fn hash(seed: u32, point: [i32; 3]) -> f32 {
+    let mut hash = seed
+        ^ (point[0] as u32).wrapping_mul(0x8da6_b343)
+        ^ (point[1] as u32).wrapping_mul(0xd816_3841)
+        ^ (point[2] as u32).wrapping_mul(0xcb1a_b31f);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0x5bd1_e995);
+    hash ^= hash >> 15;
+    (hash >> 8) as f32 / (1 << 24) as f32
+}
+
+#[derive(Clone, Copy)]
+pub enum Lattice {
+    /// Random values
+    Value,
+    /// Random gradients: Perlin noise
+    Gradient,
+}
+
+/// Smooth noise in [-1, 1] interpolated from the integer lattice
+pub struct Noise {
+    seed: u32,
+    lattice: Lattice,
+}
+
+impl Noise {
+    pub fn new<R: Rng>(rng: &mut R, lattice: Lattice) -> Self {
+        Noise {
+            seed: rng.gen(),
+            lattice,
+        }
+    }
+
+    pub fn sample(&self, position: [f32; 3]) -> f32 {
+        let floor = [position[0].floor(), position[1].floor(), position[2].floor()];
+        let delta = [position[0] - floor[0], position[1] - floor[1], position[2] - floor[2]];
+        let cell = [floor[0] as i32, floor[1] as i32, floor[2] as i32];
+
+        let mut corners = [0.0f32; 8];
+        for (i, corner) in corners.iter_mut().enumerate() {
+            let offset = [(i & 1) as i32, ((i >> 1) & 1) as i32, ((i >> 2) & 1) as i32];
+            let random = hash(self.seed, [cell[0] + offset[0], cell[1] + offset[1], cell[2] + offset[2]]);
+            *corner = match self.lattice {
+                Lattice::Value => 2.0 * random - 1.0,
+                Lattice::Gradient => {
+                    let gradient = GRADIENTS[(random * 12.0) as usize % 12];
+                    (0..3)
+                        .map(|axis| gradient[axis] * (delta[axis] - offset[axis] as f32))
+                        .sum::<f32>()
+                }
+            };
+        }
+
+        let fade = |t: f32| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+        let lerp = |a: f32, b: f32, t: f32| a + t * (b - a);
+        let (u, v, w) = (fade(delta[0]), fade(delta[1]), fade(delta[2]));
+        lerp(
+            lerp(lerp(corners[0], corners[1], u), lerp(corners[2], corners[3], u), v),
+            lerp(lerp(corners[4], corners[5], u), lerp(corners[6], corners[7], u), v),
+            w,
+        )
+    }
+}
+
+/// Octaves of noise, each one twice the frequency of the previous one
+pub struct Fractal {
+    octaves: Vec<(Noise, f32, f32)>,
+}
+
+impl Fractal {
+    /// Amplitude is multiplied by persistence at each octave
+    pub fn new<R: Rng>(rng: &mut R, lattice: Lattice, frequency: f32, octaves: usize, persistence: f32) -> Self {
+        Fractal {
+            octaves: (0..octaves)
+                .map(|octave| {
+                    (
+                        Noise::new(&mut *rng, lattice),
+                        frequency * 2_f32.powi(octave as i32),
+                        persistence.powi(octave as i32),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// Noise in [-1, 1]
+    pub fn sample(&self, position: [f32; 3]) -> f32 {
+        let mut sum = 0.0;
+        let mut total = 0.0;
+        for &(ref noise, frequency, amplitude) in &self.octaves {
+            sum += amplitude * noise.sample([
+                position[0] * frequency,
+                position[1] * frequency,
+                position[2] * frequency,
+            ]);
+            total += amplitude;
+        }
+        sum / total
+    }
+}
+
+/// One random seed in each cell of the lattice
+pub struct Voronoi {
+    seed: u32,
+    frequency: f32,
+}
+
+impl Voronoi {
+    pub fn new<R: Rng>(rng: &mut R, frequency: f32) -> Self {
+        Voronoi {
+            seed: rng.gen(),
+            frequency,
+        }
+    }
+
+    fn site(&self, cell: [i32; 3]) -> [f32; 3] {
+        [
+            cell[0] as f32 + 0.1 + 0.8 * hash(self.seed, cell),
+            cell[1] as f32 + 0.1 + 0.8 * hash(self.seed.wrapping_add(1), cell),
+            cell[2] as f32 + 0.1 + 0.8 * hash(self.seed.wrapping_add(2), cell),
+        ]
+    }
+
+    /// Random shade in [0, 1) of the nearest site and distance to the border of its region in units
+    pub fn nearest(&self, position: [f32; 3]) -> (f32, f32) {
+        let position = [
+            position[0] * self.frequency,
+            position[1] * self.frequency,
+            position[2] * self.frequency,
+        ];
+        let cell = [position[0].floor() as i32, position[1].floor() as i32, position[2].floor() as i32];
+
+        let mut sites = vec![];
+        for x in -1..2 {
+            for y in -1..2 {
+                for z in -1..2 {
+                    let neighbour = [cell[0] + x, cell[1] + y, cell[2] + z];
+                    sites.push((neighbour, self.site(neighbour)));
+                }
+            }
+        }
+
+        let distance2 = |a: &[f32; 3], b: &[f32; 3]| {
+            (a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)
+        };
+        let &(nearest_cell, nearest) = sites.iter()
+            .min_by(|a, b| {
+                distance2(&a.1, &position).partial_cmp(&distance2(&b.1, &position)).unwrap()
+            })
+            .unwrap();
+
+        // Distance to the bisector plane with each other site
+        let border = sites.iter()
+            .filter(|&&(neighbour, _)| neighbour != nearest_cell)
+            .map(|&(_, site)| {
+                (distance2(&site, &position) - distance2(&nearest, &position))
+                    / (2.0 * distance2(&site, &nearest).sqrt())
+            })
+            .fold(::std::f32::INFINITY, f32::min);
+
+        (hash(self.seed.wrapping_add(3), nearest_cell), border / self.frequency)
+    }
+}
+
 pub fn generate_texture(
     width: u32,
     height: u32,
@@ -7,7 +281,18 @@ pub fn generate_texture(
     filter: ::image::FilterType,
     absissa_continuous: bool,
 ) -> ::image::ImageBuffer<::image::Luma<u8>, Vec<u8>> {
-    let mut rng = ::rand::thread_rng();
+    generate_texture_with_rng(&mut ::rand::thread_rng(), width, height, layers, filter, absissa_continuous)
+}
+
+/// Same as generate_texture but deterministic for a given rng
+pub fn generate_texture_with_rng<R: Rng>(
+    rng: &mut R,
+    width: u32,
+    height: u32,
+    layers: u32,
+    filter: ::image::FilterType,
+    absissa_continuous: bool,
+) -> ::image::ImageBuffer<::image::Luma<u8>, Vec<u8>> {
     let mut deepness = layers - 1;
 
     let tmp_width = if absissa_continuous { width * 2 } else { width };
@@ -22,7 +307,7 @@ pub fn generate_texture(
 
         let data = (0..sub_image_width*sub_image_height)
             // IDEA: other distributions for example: [0..1]^2 * 255
-            .map(|_| Standard.sample(&mut rng))
+            .map(|_| Standard.sample(&mut *rng))
             .collect::<Vec<_>>();
 
         let image =
@@ -1,7 +1,7 @@
 use rand::{thread_rng, Rng};
 use std::collections::HashSet;
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, EnumIterator)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, EnumIterator, Serialize, Deserialize)]
 pub enum TileSize {
     T1x1,
     T1x2,
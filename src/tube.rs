@@ -5,7 +5,7 @@ use std::f32::consts::{FRAC_PI_2, PI};
 
 pub const RADIUS: f32 = 0.05;
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, EnumIterator)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, EnumIterator, Serialize, Deserialize)]
 pub enum Shape {
     /// Line along y axis
     Line,
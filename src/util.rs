@@ -33,8 +33,8 @@ pub fn to_world(coords: &::na::Vector3<isize>, scale: f32) -> ::na::Vector3<f32>
     res
 }
 
-pub fn menu_layout(texts: Vec<String>, cursor: Option<usize>, font: &::rusttype::Font<'static>) -> Vec<::rusttype::PositionedGlyph<'static>> {
-    let v_metrics = font.v_metrics(::rusttype::Scale::uniform(::CFG.text_scale));
+pub fn menu_layout(texts: Vec<String>, cursor: Option<usize>, font: &::rusttype::Font<'static>, text_scale: f32) -> Vec<::rusttype::PositionedGlyph<'static>> {
+    let v_metrics = font.v_metrics(::rusttype::Scale::uniform(text_scale));
     let y_delta = v_metrics.ascent - v_metrics.descent + v_metrics.line_gap;
     let y_top = (texts.len() / 2) as f32 * y_delta;
 
@@ -48,7 +48,7 @@ pub fn menu_layout(texts: Vec<String>, cursor: Option<usize>, font: &::rusttype:
         })
         .flat_map(|(i, text)| {
             let y = i as f32 * y_delta - y_top;
-            let scale = ::rusttype::Scale::uniform(::CFG.text_scale);
+            let scale = ::rusttype::Scale::uniform(text_scale);
             let point = ::rusttype::point(0.0, y);
             let mut glyphs = font.layout(text, scale, point)
                 .map(|x| x.standalone())
@@ -75,8 +75,8 @@ pub fn menu_layout(texts: Vec<String>, cursor: Option<usize>, font: &::rusttype:
         .collect::<Vec<_>>()
 }
 
-pub fn joystick_description_layout(texts: Vec<String>, delim: char, menu_len: usize, font: &::rusttype::Font<'static>) -> Vec<::rusttype::PositionedGlyph<'static>> {
-    let v_metrics = font.v_metrics(::rusttype::Scale::uniform(::CFG.text_scale));
+pub fn joystick_description_layout(texts: Vec<String>, delim: char, menu_len: usize, font: &::rusttype::Font<'static>, text_scale: f32) -> Vec<::rusttype::PositionedGlyph<'static>> {
+    let v_metrics = font.v_metrics(::rusttype::Scale::uniform(text_scale));
     let y_delta = v_metrics.ascent - v_metrics.descent + v_metrics.line_gap;
     let y_top = (menu_len / 2 + texts.len() + 1) as f32 * y_delta;
     let delim_id = delim.into_glyph_id(font);
@@ -85,7 +85,7 @@ pub fn joystick_description_layout(texts: Vec<String>, delim: char, menu_len: us
         .enumerate()
         .flat_map(|(i, text)| {
             let y = i as f32 * y_delta - y_top;
-            let scale = ::rusttype::Scale::uniform(::CFG.text_scale);
+            let scale = ::rusttype::Scale::uniform(text_scale);
             let point = ::rusttype::point(0.0, y);
             let mut glyphs = font.layout(text, scale, point)
                 .map(|x| x.standalone())
@@ -108,3 +108,20 @@ pub fn joystick_description_layout(texts: Vec<String>, delim: char, menu_len: us
         })
         .collect::<Vec<_>>()
 }
+
+/// Left aligned lines starting at the origin
+pub fn notification_layout(texts: Vec<String>, font: &::rusttype::Font<'static>, text_scale: f32) -> Vec<::rusttype::PositionedGlyph<'static>> {
+    let scale = ::rusttype::Scale::uniform(text_scale);
+    let v_metrics = font.v_metrics(scale);
+    let y_delta = v_metrics.ascent - v_metrics.descent + v_metrics.line_gap;
+
+    texts.iter()
+        .enumerate()
+        .flat_map(|(i, text)| {
+            let point = ::rusttype::point(0.0, v_metrics.ascent + i as f32 * y_delta);
+            font.layout(text, scale, point)
+                .map(|x| x.standalone())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>()
+}
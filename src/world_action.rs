@@ -3,17 +3,28 @@ use retained_storage::Retained;
 pub trait WorldAction {
     fn safe_maintain(&mut self);
     fn reset_for_mode(&mut self);
+    /// Return the slot given to the controller if any
+    fn join_player(&mut self, controller: ::resource::Controller) -> Option<usize>;
+    fn leave_player(&mut self, player: usize);
+    fn swap_players(&mut self, player_1: usize, player_2: usize);
+    /// Return the slot given back to the gamepad if any
+    fn gamepad_connected(&mut self, id: usize) -> Option<usize>;
+    /// Return the slot released by the gamepad if any
+    fn gamepad_disconnected(&mut self, id: usize) -> Option<usize>;
 }
 
 // Maintain world and synchronize physic world
 fn safe_maintain(world: &mut ::specs::World) {
     world.maintain();
     let mut physic_world = world.write_resource::<::resource::PhysicWorld>();
+    let mut physic_entities = world.write_resource::<::resource::PhysicEntities>();
     for body in world.write_storage::<::component::PhysicBody>().retained() {
         physic_world.remove_rigid_body(body.handle());
+        physic_entities.remove_body(body.handle());
     }
-    for sensor in world.write_storage::<::component::PhysicBody>().retained() {
+    for sensor in world.write_storage::<::component::PhysicSensor>().retained() {
         physic_world.remove_sensor(sensor.handle());
+        physic_entities.remove_sensor(sensor.handle());
     }
 }
 
@@ -26,12 +37,14 @@ impl WorldAction for ::specs::World {
         safe_maintain(self);
         {
             let mut players_controllers = self.write_resource::<::resource::PlayersControllers>();
+            let mut disconnected_gamepads = self.write_resource::<::resource::DisconnectedGamepads>();
             let mut players_entities = self.write_resource::<::resource::PlayersEntities>();
             let mode = self.read_resource::<::resource::Mode>();
             let entities = self.entities();
 
             for player in mode.number_of_player()..3 {
                 players_controllers[player] = None;
+                disconnected_gamepads[player] = None;
                 if let Some(entity) = players_entities[player].take() {
                     entities.delete(entity).unwrap();
                 }
@@ -39,4 +52,77 @@ impl WorldAction for ::specs::World {
         }
         safe_maintain(self);
     }
+
+    fn join_player(&mut self, controller: ::resource::Controller) -> Option<usize> {
+        let mut players_controllers = self.write_resource::<::resource::PlayersControllers>();
+        let mut disconnected_gamepads = self.write_resource::<::resource::DisconnectedGamepads>();
+        let mode = self.read_resource::<::resource::Mode>();
+
+        let (already_playing, previous_slot) = match controller {
+            ::resource::Controller::Gamepad(id) => (
+                players_controllers.gamepad_player(id),
+                disconnected_gamepads.iter().position(|&gamepad| gamepad == Some(id)),
+            ),
+            ::resource::Controller::Keyboard(_) => (None, None),
+        };
+
+        if already_playing.is_some() {
+            return already_playing;
+        }
+
+        // Slots kept for a disconnected gamepad are only taken if no other slot is free
+        let player = previous_slot
+            .or_else(|| {
+                (0..mode.number_of_player()).find(|&player| {
+                    players_controllers[player].is_none() && disconnected_gamepads[player].is_none()
+                })
+            })
+            .or_else(|| {
+                (0..mode.number_of_player()).find(|&player| players_controllers[player].is_none())
+            });
+
+        if let Some(player) = player {
+            players_controllers[player] = Some(controller);
+            disconnected_gamepads[player] = None;
+        }
+        player
+    }
+
+    fn leave_player(&mut self, player: usize) {
+        self.write_resource::<::resource::PlayersControllers>()[player] = None;
+        self.write_resource::<::resource::DisconnectedGamepads>()[player] = None;
+    }
+
+    fn swap_players(&mut self, player_1: usize, player_2: usize) {
+        self.write_resource::<::resource::PlayersControllers>().swap(player_1, player_2);
+        self.write_resource::<::resource::DisconnectedGamepads>().swap(player_1, player_2);
+        self.write_resource::<::resource::PlayersEntities>().swap(player_1, player_2);
+    }
+
+    fn gamepad_connected(&mut self, id: usize) -> Option<usize> {
+        let mut players_controllers = self.write_resource::<::resource::PlayersControllers>();
+        let mut disconnected_gamepads = self.write_resource::<::resource::DisconnectedGamepads>();
+
+        let player = disconnected_gamepads.iter()
+            .position(|&gamepad| gamepad == Some(id))
+            .filter(|&player| players_controllers[player].is_none());
+
+        if let Some(player) = player {
+            players_controllers[player] = Some(::resource::Controller::Gamepad(id));
+            disconnected_gamepads[player] = None;
+        }
+        player
+    }
+
+    fn gamepad_disconnected(&mut self, id: usize) -> Option<usize> {
+        let mut players_controllers = self.write_resource::<::resource::PlayersControllers>();
+        let mut disconnected_gamepads = self.write_resource::<::resource::DisconnectedGamepads>();
+
+        let player = players_controllers.gamepad_player(id);
+        if let Some(player) = player {
+            players_controllers[player] = None;
+            disconnected_gamepads[player] = Some(id);
+        }
+        player
+    }
 }